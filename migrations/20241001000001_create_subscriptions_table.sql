@@ -0,0 +1,18 @@
+-- 基线迁移（0001 - 0008）都允许在已由旧的 `pgsql.sql` 建好的数据库上执行：
+-- 已存在的表会被跳过，只补上 `pgsql.sql` 中缺少的部分
+
+-- 订阅者
+CREATE TABLE IF NOT EXISTS subscriptions (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    -- pending_confirmation / confirmed / unsubscribed
+    status TEXT NOT NULL
+);
+
+-- `pgsql.sql` 建的表没有 `status` 列，旧数据都视为已确认
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS status TEXT NULL;
+UPDATE subscriptions SET status = 'confirmed' WHERE status IS NULL;
+ALTER TABLE subscriptions ALTER COLUMN status SET NOT NULL;
@@ -0,0 +1,12 @@
+-- 订阅确认令牌
+CREATE TABLE IF NOT EXISTS subscription_tokens (
+    subscription_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscription_token)
+);
+
+-- `pgsql.sql` 建的表没有 `created_at` 列，已有的令牌从现在开始计算有效期
+ALTER TABLE subscription_tokens
+    ADD COLUMN IF NOT EXISTS created_at timestamptz NOT NULL DEFAULT now();
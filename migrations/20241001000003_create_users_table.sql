@@ -0,0 +1,6 @@
+-- 后台管理员
+CREATE TABLE IF NOT EXISTS users (
+    user_id uuid PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL
+);
@@ -0,0 +1,28 @@
+-- `CREATE TYPE` 不支持 `IF NOT EXISTS`
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'header_pair') THEN
+        CREATE TYPE header_pair AS (
+            name TEXT,
+            value BYTEA
+        );
+    END IF;
+END
+$$;
+
+-- 响应相关的列在请求处理完成之前为 NULL
+CREATE TABLE IF NOT EXISTS idempotency (
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    idempotency_key TEXT NOT NULL,
+    response_status_code SMALLINT NULL,
+    response_headers header_pair[] NULL,
+    response_body BYTEA NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (user_id, idempotency_key)
+);
+
+-- `pgsql.sql` 中这几列是 NOT NULL
+ALTER TABLE idempotency
+    ALTER COLUMN response_status_code DROP NOT NULL,
+    ALTER COLUMN response_headers DROP NOT NULL,
+    ALTER COLUMN response_body DROP NOT NULL;
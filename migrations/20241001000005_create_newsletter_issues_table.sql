@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS newsletter_issues (
+    newsletter_issue_id uuid NOT NULL,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    -- 计划发布的期刊在进入投递队列之前为 NULL
+    published_at TEXT NULL,
+    scheduled_for timestamptz NULL,
+    PRIMARY KEY (newsletter_issue_id)
+);
+
+-- `pgsql.sql` 建的表没有 `scheduled_for` 列，且 `published_at` 是 NOT NULL
+ALTER TABLE newsletter_issues ADD COLUMN IF NOT EXISTS scheduled_for timestamptz NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS issue_delivery_queue (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    n_retries SMALLINT NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+-- `pgsql.sql` 建的表没有重试相关的列，队列中已有的任务视为尚未重试、可以立即执行
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN IF NOT EXISTS n_retries SMALLINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS execute_after timestamptz NOT NULL DEFAULT now();
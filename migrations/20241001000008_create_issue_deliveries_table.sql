@@ -0,0 +1,11 @@
+-- 每封期刊邮件的最终投递结果
+CREATE TABLE IF NOT EXISTS issue_deliveries (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    -- sent / failed / skipped_invalid_address / skipped_unsubscribed
+    status TEXT NOT NULL,
+    provider_message_id TEXT NULL,
+    recorded_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
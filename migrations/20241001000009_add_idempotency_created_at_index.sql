@@ -0,0 +1,2 @@
+-- 清理过期幂等键时按 created_at 查找
+CREATE INDEX idempotency_created_at_idx ON idempotency (created_at);
@@ -0,0 +1,3 @@
+-- 确认链接被使用后不再删除令牌，而是记录使用时间，
+-- 这样再次点击时仍然可以告诉订阅者“已经确认过了”
+ALTER TABLE subscription_tokens ADD COLUMN used_at timestamptz NULL;
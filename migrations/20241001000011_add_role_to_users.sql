@@ -0,0 +1,7 @@
+-- 后台用户的角色与启用状态，已有用户都视为 owner
+-- email 用于发送邀请，通过 SQL 直接创建的用户可以没有
+ALTER TABLE users
+    ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+        CHECK (role IN ('owner', 'editor', 'viewer')),
+    ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT true,
+    ADD COLUMN email TEXT NULL UNIQUE;
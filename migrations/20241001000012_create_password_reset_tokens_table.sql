@@ -0,0 +1,10 @@
+-- 重置密码的令牌，只保存令牌的 SHA-256 摘要，数据库泄漏时无法直接使用
+CREATE TABLE password_reset_tokens (
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    created_at timestamptz NOT NULL,
+    used_at timestamptz NULL
+);
+
+-- 在此时间之前登录的会话都视为失效
+ALTER TABLE users ADD COLUMN sessions_revoked_at timestamptz NULL;
@@ -0,0 +1,10 @@
+-- TOTP 密钥（base32 编码），为 NULL 表示该用户没有启用两步验证
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+
+-- 两步验证的恢复码，与重置令牌一样只保存 SHA-256 摘要，每个恢复码只能使用一次
+CREATE TABLE recovery_codes (
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,10 @@
+-- 登录失败计数，分别按用户名和客户端 IP 统计，用于渐进式延迟和临时锁定
+-- 用户名按提交的原样记录，不管账号是否存在，避免通过锁定行为探测账号
+CREATE TABLE login_failures (
+    scope TEXT NOT NULL CHECK (scope IN ('username', 'ip')),
+    key TEXT NOT NULL,
+    failures INT NOT NULL,
+    last_failed_at timestamptz NOT NULL,
+    locked_until timestamptz NULL,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,13 @@
+-- 供脚本调用 API 使用的令牌，与重置令牌一样只保存 SHA-256 摘要
+-- `token_prefix` 是令牌明文的前几位，只用于在列表中帮助辨认
+CREATE TABLE api_tokens (
+    api_token_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    name TEXT NOT NULL,
+    scope TEXT NOT NULL CHECK (scope IN ('newsletters:publish', 'newsletters:read')),
+    token_hash TEXT NOT NULL UNIQUE,
+    token_prefix TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL
+);
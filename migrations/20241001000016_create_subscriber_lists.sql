@@ -0,0 +1,35 @@
+-- 订阅列表：每个列表对应一份独立的刊物，订阅者可以加入多个列表
+CREATE TABLE subscriber_lists (
+    list_id uuid PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- 已有的订阅者和期刊都归入默认列表，行为与之前的单一刊物保持一致
+INSERT INTO subscriber_lists (list_id, slug, name)
+VALUES (gen_random_uuid(), 'newsletter', 'Newsletter');
+
+CREATE TABLE list_memberships (
+    list_id uuid NOT NULL REFERENCES subscriber_lists (list_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    joined_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+INSERT INTO list_memberships (list_id, subscriber_id)
+SELECT l.list_id, s.id
+FROM subscriptions s, subscriber_lists l
+WHERE l.slug = 'newsletter';
+
+-- 期刊发送的目标列表
+CREATE TABLE newsletter_issue_lists (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    list_id uuid NOT NULL REFERENCES subscriber_lists (list_id),
+    PRIMARY KEY (newsletter_issue_id, list_id)
+);
+
+INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+SELECT i.newsletter_issue_id, l.list_id
+FROM newsletter_issues i, subscriber_lists l
+WHERE l.slug = 'newsletter';
@@ -0,0 +1,18 @@
+-- `published_at` 之前以 TEXT 保存 now() 的输出，改为 timestamptz 才能可靠地排序和按日期筛选
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+CREATE INDEX newsletter_issues_published_at_idx ON newsletter_issues (published_at);
+
+-- 创建期刊的管理员，之前的期刊无从得知，保留为 NULL
+ALTER TABLE newsletter_issues
+    ADD COLUMN created_by uuid NULL REFERENCES users (user_id);
+
+-- scheduled / published
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NULL;
+UPDATE newsletter_issues
+SET status = CASE WHEN published_at IS NULL THEN 'scheduled' ELSE 'published' END;
+ALTER TABLE newsletter_issues ALTER COLUMN status SET NOT NULL;
+
+ALTER TABLE newsletter_issues ADD COLUMN updated_at timestamptz NULL;
+UPDATE newsletter_issues SET updated_at = COALESCE(published_at, now());
+ALTER TABLE newsletter_issues ALTER COLUMN updated_at SET NOT NULL;
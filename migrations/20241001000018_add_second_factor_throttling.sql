@@ -0,0 +1,8 @@
+-- 最近一次通过验证的 TOTP 时间步，不晚于它的验证码都会被拒绝，防止验证码被重放
+-- 更换或停用密钥时重置为 NULL
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT NULL;
+
+-- 第二因素的失败次数按用户 id 统计，与密码的失败次数分开
+ALTER TABLE login_failures DROP CONSTRAINT login_failures_scope_check;
+ALTER TABLE login_failures ADD CONSTRAINT login_failures_scope_check
+    CHECK (scope IN ('username', 'ip', 'second_factor'));
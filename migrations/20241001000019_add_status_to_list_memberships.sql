@@ -0,0 +1,5 @@
+-- 加入列表同样需要订阅者确认：新的成员关系在点击确认链接之前处于待确认状态
+-- 已有的成员关系都视为已确认，与之前的行为保持一致
+ALTER TABLE list_memberships ADD COLUMN status TEXT NULL;
+UPDATE list_memberships SET status = 'confirmed';
+ALTER TABLE list_memberships ALTER COLUMN status SET NOT NULL;
@@ -0,0 +1,6 @@
+-- 导入订阅者时需要发送的确认邮件，由后台 worker 发送，导入请求不必等待邮件服务
+CREATE TABLE confirmation_email_queue (
+    subscriber_id uuid PRIMARY KEY REFERENCES subscriptions (id),
+    n_retries SMALLINT NOT NULL,
+    execute_after timestamptz NOT NULL
+);
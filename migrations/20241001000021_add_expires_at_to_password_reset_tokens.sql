@@ -0,0 +1,5 @@
+-- 重置密码和邀请新用户共用这张表，但有效期不同，所以在签发时记录过期时间
+ALTER TABLE password_reset_tokens ADD COLUMN expires_at timestamptz NULL;
+-- 已有的令牌按默认的 30 分钟有效期计算
+UPDATE password_reset_tokens SET expires_at = created_at + interval '30 minutes';
+ALTER TABLE password_reset_tokens ALTER COLUMN expires_at SET NOT NULL;
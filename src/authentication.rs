@@ -1,10 +1,26 @@
+mod api_token;
 mod middleware;
 mod password;
+mod role;
+mod throttling;
+mod two_factor;
 
+pub use api_token::{
+    create_api_token, get_api_tokens, revoke_api_token, validate_api_token, ApiTokenOwner,
+    ApiTokenScope, ApiTokenSummary,
+};
 pub use password:: {
-    change_password, validate_credentials,
-    AuthError, Credentials,
+    change_password, create_user, validate_credentials,
+    AuthError, Credentials, SetPasswordError,
 };
 
-pub use middleware::reject_anonymous_users;
+pub use middleware::{reject_anonymous_users, reject_non_owners, reject_viewers};
 pub use middleware::UserId;
+pub use role::Role;
+pub use throttling::{
+    validate_credentials_with_throttling, verify_second_factor_with_throttling,
+};
+pub use two_factor::{
+    disable_two_factor, enable_two_factor, generate_totp_secret, get_totp_secret,
+    otpauth_qr_code, totp, verify_second_factor,
+};
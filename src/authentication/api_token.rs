@@ -0,0 +1,234 @@
+use crate::authentication::{AuthError, Role};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distr::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// 固定前缀方便在日志或代码仓库中识别出泄露的令牌
+const TOKEN_PREFIX: &str = "z2p_";
+const TOKEN_LENGTH: usize = 40;
+const DISPLAYED_PREFIX_LENGTH: usize = 8;
+
+/// API 令牌的权限范围
+///
+/// - `PublishNewsletters` 可以发布期刊，也可以查看期刊的投递状态
+/// - `ReadNewsletters` 只能查看期刊的投递状态
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ApiTokenScope {
+    PublishNewsletters,
+    ReadNewsletters,
+}
+
+impl ApiTokenScope {
+    pub const ALL: [ApiTokenScope; 2] = [
+        ApiTokenScope::PublishNewsletters,
+        ApiTokenScope::ReadNewsletters,
+    ];
+
+    pub fn parse(s: &str) -> Result<ApiTokenScope, String> {
+        match s {
+            "newsletters:publish" => Ok(ApiTokenScope::PublishNewsletters),
+            "newsletters:read" => Ok(ApiTokenScope::ReadNewsletters),
+            other => Err(format!("{} is not a valid API token scope.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::PublishNewsletters => "newsletters:publish",
+            ApiTokenScope::ReadNewsletters => "newsletters:read",
+        }
+    }
+
+    pub fn grants(&self, required: ApiTokenScope) -> bool {
+        *self == required || *self == ApiTokenScope::PublishNewsletters
+    }
+
+    // 令牌不能拥有比创建者角色更大的权限
+    pub fn is_allowed_for(&self, role: Role) -> bool {
+        match self {
+            ApiTokenScope::PublishNewsletters => role.can_publish(),
+            ApiTokenScope::ReadNewsletters => true,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiTokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 令牌列表中展示的信息，不包含令牌本身
+pub struct ApiTokenSummary {
+    pub api_token_id: Uuid,
+    pub name: String,
+    pub scope: String,
+    pub token_prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// 通过 API 令牌认证的调用方
+pub struct ApiTokenOwner {
+    pub user_id: Uuid,
+    pub role: Role,
+    pub scope: ApiTokenScope,
+}
+
+fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_api_token() -> String {
+    let mut rng = thread_rng();
+    let random: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(TOKEN_LENGTH)
+        .collect();
+    format!("{}{}", TOKEN_PREFIX, random)
+}
+
+/// 创建一个新令牌，令牌明文只在这里返回一次，数据库中只保存摘要
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scope: ApiTokenScope,
+    pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    let token = generate_api_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (api_token_id, user_id, name, scope, token_hash, token_prefix)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        scope.as_str(),
+        hash_api_token(&token),
+        &token[..TOKEN_PREFIX.len() + DISPLAYED_PREFIX_LENGTH],
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a new API token.")?;
+    Ok(Secret::new(token))
+}
+
+#[tracing::instrument(name = "Get API tokens", skip(pool))]
+pub async fn get_api_tokens(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Vec<ApiTokenSummary>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiTokenSummary,
+        r#"
+        SELECT api_token_id, name, scope, token_prefix, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY revoked_at IS NOT NULL, created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve API tokens.")?;
+    Ok(tokens)
+}
+
+/// 吊销令牌，返回 `false` 表示令牌不存在、不属于该用户或已经被吊销
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    api_token_id: Uuid,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_revoked = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE api_token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        api_token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke an API token.")?
+    .rows_affected();
+    Ok(n_revoked == 1)
+}
+
+/// 校验 `Authorization: Bearer` 中的令牌
+///
+/// 已吊销的令牌和已停用用户的令牌都视为无效。调用方还需要检查 `scope` 是否满足要求。
+#[tracing::instrument(name = "Validate API token", skip(token, pool))]
+pub async fn validate_api_token(
+    token: Secret<String>,
+    pool: &PgPool,
+) -> Result<ApiTokenOwner, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens t
+        SET last_used_at = now()
+        FROM users u
+        WHERE t.token_hash = $1
+            AND t.revoked_at IS NULL
+            AND u.user_id = t.user_id
+            AND u.is_active
+        RETURNING t.user_id, t.scope, u.role
+        "#,
+        hash_api_token(token.expose_secret()),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to validate an API token.")?
+    .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown API token.")))?;
+
+    let role = Role::parse(&row.role).map_err(anyhow::Error::msg)?;
+    let scope = ApiTokenScope::parse(&row.scope).map_err(anyhow::Error::msg)?;
+    Ok(ApiTokenOwner {
+        user_id: row.user_id,
+        role,
+        scope,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_api_token, ApiTokenScope};
+    use crate::authentication::Role;
+
+    #[test]
+    fn every_scope_round_trips_through_its_string_form() {
+        for scope in ApiTokenScope::ALL {
+            assert_eq!(ApiTokenScope::parse(scope.as_str()), Ok(scope));
+        }
+    }
+
+    #[test]
+    fn the_publish_scope_also_grants_read_access() {
+        assert!(ApiTokenScope::PublishNewsletters.grants(ApiTokenScope::ReadNewsletters));
+        assert!(!ApiTokenScope::ReadNewsletters.grants(ApiTokenScope::PublishNewsletters));
+    }
+
+    #[test]
+    fn viewers_cannot_hold_a_publish_token() {
+        assert!(!ApiTokenScope::PublishNewsletters.is_allowed_for(Role::Viewer));
+        assert!(ApiTokenScope::ReadNewsletters.is_allowed_for(Role::Viewer));
+    }
+
+    #[test]
+    fn generated_tokens_are_prefixed_and_distinct() {
+        let (a, b) = (generate_api_token(), generate_api_token());
+        assert!(a.starts_with("z2p_"));
+        assert_eq!(a.len(), 44);
+        assert_ne!(a, b);
+    }
+}
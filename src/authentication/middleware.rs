@@ -1,10 +1,14 @@
+use crate::authentication::Role;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use std::fmt::Debug;
 use std::ops::Deref;
 use uuid::Uuid;
@@ -35,15 +39,113 @@ pub async fn reject_anonymous_users(
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
+        None => {
+            // 只通过了密码验证的会话要先完成两步验证
+            let location = match session.get_pending_second_factor().map_err(e500)? {
+                Some(_) => "/login/two-factor",
+                None => "/login",
+            };
+            let response = see_other(location);
+            let e = anyhow::anyhow!("The user has not logged in");
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+
+    // 每次请求都重新读取角色，这样停用或降级会立刻生效，而不必等会话过期
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The connection pool is not registered as application data.");
+    let user = get_active_user(user_id, pool).await.map_err(e500)?;
+    // 重置密码会吊销该用户此前建立的所有会话
+    let logged_in_at = session.get_logged_in_at().map_err(e500)?;
+    match user {
+        Some(user) if !user.revokes_session_from(logged_in_at) => {
             req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(user.role);
             next.call(req).await
         }
-        None => {
+        _ => {
+            session.log_out();
             let response = see_other("/login");
-            let e = anyhow::anyhow!("The user has not logged in");
+            let e = anyhow::anyhow!("The user has been deactivated or the session was revoked");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+// 必须放在 `reject_anonymous_users` 之内使用，它负责把 `Role` 放进请求扩展中
+pub async fn reject_viewers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let can_publish = req
+        .extensions()
+        .get::<Role>()
+        .is_some_and(Role::can_publish);
+    if !can_publish {
+        return Err(forbidden("The user is not allowed to modify newsletters"));
+    }
+    next.call(req).await
+}
+
+pub async fn reject_non_owners(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let can_manage_users = req
+        .extensions()
+        .get::<Role>()
+        .is_some_and(Role::can_manage_users);
+    if !can_manage_users {
+        return Err(forbidden("The user is not allowed to manage users"));
+    }
+    next.call(req).await
+}
+
+fn forbidden(message: &'static str) -> actix_web::Error {
+    let response = HttpResponse::Forbidden().finish();
+    InternalError::from_response(anyhow::anyhow!(message), response).into()
+}
+
+struct ActiveUser {
+    role: Role,
+    sessions_revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ActiveUser {
+    fn revokes_session_from(&self, logged_in_at: Option<DateTime<Utc>>) -> bool {
+        match (self.sessions_revoked_at, logged_in_at) {
+            (Some(revoked_at), Some(logged_in_at)) => logged_in_at < revoked_at,
+            // 没有记录登录时间的旧会话只要发生过吊销就一律失效
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get an active user", skip(pool))]
+async fn get_active_user(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<ActiveUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT role, sessions_revoked_at
+        FROM users
+        WHERE user_id = $1 AND is_active
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve an active user.")?;
+    row.map(|r| {
+        Ok(ActiveUser {
+            role: Role::parse(&r.role).map_err(|e| anyhow::anyhow!(e))?,
+            sessions_revoked_at: r.sessions_revoked_at,
+        })
+    })
+    .transpose()
+}
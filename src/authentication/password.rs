@@ -1,17 +1,34 @@
 
+use crate::authentication::Role;
+use crate::domain::{PasswordPolicy, PasswordPolicyViolation};
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use rand::distr::Alphanumeric;
+use rand::{thread_rng, Rng};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
 
 #[derive(thiserror::Error, Debug)]
 
 pub enum AuthError {
     #[error("Invalid credentials.")]
     InvalidCredentials(#[source] anyhow::Error),
+    // 失败次数过多被临时锁定，附带距离解除锁定的时间
+    #[error("Too many failed attempts.")]
+    TooManyAttempts(std::time::Duration),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+// 设置新密码时可能出现的错误，违反密码策略时会列出所有违反的规则
+#[derive(thiserror::Error, Debug)]
+pub enum SetPasswordError {
+    #[error("The new password does not satisfy the password policy.")]
+    PolicyViolations(Vec<PasswordPolicyViolation>),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -21,15 +38,23 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+// 在调用方的事务中更新密码，调用方可以把它和其他步骤（例如作废重置令牌）一起提交
+#[tracing::instrument(name = "Change password", skip(password, transaction))]
 pub async fn change_password(
+    transaction: &mut Transaction<'_, Postgres>,
     user_id: uuid::Uuid,
     password: Secret<String>,
-    pool: &PgPool,
-) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+) -> Result<(), SetPasswordError> {
+    let current_password_hash = sqlx::query!(
+        r#"SELECT password_hash FROM users WHERE user_id = $1"#,
+        user_id
+    )
+        .fetch_one(&mut **transaction)
+        .await
+        .context("Failed to retrieve the current password hash.")?
+        .password_hash;
+    let password_hash =
+        hash_new_password(password, Some(Secret::new(current_password_hash))).await?;
     sqlx::query!(
         r#"
         UPDATE users
@@ -39,12 +64,75 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-        .execute(pool)
+        .execute(&mut **transaction)
         .await
         .context("Failed to change user's password in the database.")?;
     Ok(())
 }
 
+// 在调用方的事务中插入新用户，调用方可以在后续步骤（例如发送邀请邮件）失败时回滚
+//
+// 新用户还没有密码：保存的是一个随机生成、随即丢弃的密码的哈希，没有人能用它登录，
+// 用户通过邀请邮件中的一次性链接设置自己的密码
+#[tracing::instrument(name = "Create user", skip(transaction))]
+pub async fn create_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    username: &str,
+    email: &str,
+    role: Role,
+) -> Result<Uuid, anyhow::Error> {
+    let unusable_password = Secret::new(
+        std::iter::repeat_with(|| thread_rng().sample(Alphanumeric))
+            .map(char::from)
+            .take(32)
+            .collect(),
+    );
+    let password_hash = spawn_blocking_with_tracing(move || {
+        compute_password_hash(unusable_password)
+    })
+        .await
+        .context("Failed to spawn blocking task.")??;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, role, password_hash)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username,
+        email,
+        role.as_str(),
+        password_hash.expose_secret(),
+    )
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to insert a new user in the database.")?;
+    Ok(user_id)
+}
+
+// 所有设置新密码的路径都必须经过这里，确保密码策略不会被绕过
+async fn hash_new_password(
+    password: Secret<String>,
+    current_password_hash: Option<Secret<String>>,
+) -> Result<Secret<String>, SetPasswordError> {
+    spawn_blocking_with_tracing(move || {
+        let mut violations = PasswordPolicy::default().check(&password);
+        if let Some(current_password_hash) = current_password_hash {
+            if verify_password_hash(current_password_hash, password.clone()).is_ok() {
+                violations.push(PasswordPolicyViolation::ReusesCurrentPassword);
+            }
+        }
+        if !violations.is_empty() {
+            return Err(SetPasswordError::PolicyViolations(violations));
+        }
+        compute_password_hash(password)
+            .context("Failed to hash password")
+            .map_err(SetPasswordError::UnexpectedError)
+    })
+        .await
+        .context("Failed to spawn blocking task.")?
+}
+
 fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
     let mut rng = OsRng;
     let salt = SaltString::generate(&mut rng);
@@ -86,7 +174,7 @@ pub async fn validate_credentials(
 
     // 如果我们在存储中找到凭证，则仅将其设置为 Some
     // 因此，即使默认密码最终（以某种方式）与提供的密码匹配，
-    // 我们永远不会对不存在的用户进行身份验证。
+    // 我们永远不会对不存在（或已停用）的用户进行身份验证。
     // 您可以轻松地为该精确场景添加单元测试。
     user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
@@ -123,7 +211,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND is_active
         "#,
         username,
     )
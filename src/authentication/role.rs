@@ -0,0 +1,71 @@
+/// 后台用户的角色
+///
+/// - `Owner` 可以做任何事，包括管理其他后台用户
+/// - `Editor` 可以发布、调度期刊以及处理失败的投递
+/// - `Viewer` 只能查看后台页面
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn parse(s: &str) -> Result<Role, String> {
+        match s {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            other => Err(format!("{} is not a valid role.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn can_publish(&self) -> bool {
+        matches!(self, Role::Owner | Role::Editor)
+    }
+
+    pub fn can_manage_users(&self) -> bool {
+        matches!(self, Role::Owner)
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+    use claim::assert_err;
+
+    #[test]
+    fn every_role_round_trips_through_its_string_form() {
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.as_str()), Ok(role));
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(Role::parse("admin"));
+    }
+
+    #[test]
+    fn only_viewers_cannot_publish() {
+        assert!(Role::Owner.can_publish());
+        assert!(Role::Editor.can_publish());
+        assert!(!Role::Viewer.can_publish());
+    }
+}
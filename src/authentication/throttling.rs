@@ -0,0 +1,309 @@
+use crate::authentication::{validate_credentials, verify_second_factor, AuthError, Credentials};
+use crate::configuration::LoginThrottlingSettings;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::time::Duration;
+use uuid::Uuid;
+
+const USERNAME_SCOPE: &str = "username";
+const IP_SCOPE: &str = "ip";
+// 按用户 id 统计第二因素的失败次数，和密码失败分开计算，换一个会话也不会清零
+const SECOND_FACTOR_SCOPE: &str = "second_factor";
+
+enum Throttle {
+    Delay(Duration),
+    LockedOut(Duration),
+}
+
+/// 在 `validate_credentials` 之前检查失败计数，并在之后更新它
+///
+/// 被锁定的用户名或 IP 直接返回 `AuthError::TooManyAttempts`，不会再计算 Argon2 哈希。
+/// 计数按提交的用户名统计，不管账号是否存在，所以锁定行为也不会暴露账号是否存在。
+#[tracing::instrument(
+    name = "Validate credentials with throttling",
+    skip(credentials, settings, pool)
+)]
+pub async fn validate_credentials_with_throttling(
+    credentials: Credentials,
+    client_ip: Option<IpAddr>,
+    settings: &LoginThrottlingSettings,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let username = credentials.username.clone();
+    let client_ip = client_ip.map(|ip| ip.to_string());
+
+    throttle(
+        USERNAME_SCOPE,
+        &username,
+        client_ip.as_deref(),
+        settings,
+        pool,
+    )
+    .await?;
+
+    match validate_credentials(credentials, pool).await {
+        Ok(user_id) => {
+            // 只清除用户名的计数，IP 的计数不能因为攻击者掌握一个账号就被重置
+            clear_failures(USERNAME_SCOPE, &username, pool).await?;
+            Ok(user_id)
+        }
+        Err(AuthError::InvalidCredentials(e)) => {
+            record_failure(
+                USERNAME_SCOPE,
+                &username,
+                settings.max_failures_per_username,
+                settings,
+                pool,
+            )
+            .await?;
+            if let Some(client_ip) = &client_ip {
+                record_failure(
+                    IP_SCOPE,
+                    client_ip,
+                    settings.max_failures_per_ip,
+                    settings,
+                    pool,
+                )
+                .await?;
+            }
+            Err(AuthError::InvalidCredentials(e))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 和 `validate_credentials_with_throttling` 一样限流第二因素的验证
+///
+/// 验证码错误时返回 `AuthError::InvalidCredentials`，失败计数按用户 id 和客户端 IP 累加。
+#[tracing::instrument(
+    name = "Verify second factor with throttling",
+    skip(code, settings, pool)
+)]
+pub async fn verify_second_factor_with_throttling(
+    user_id: Uuid,
+    code: &str,
+    client_ip: Option<IpAddr>,
+    settings: &LoginThrottlingSettings,
+    pool: &PgPool,
+) -> Result<(), AuthError> {
+    let key = user_id.to_string();
+    let client_ip = client_ip.map(|ip| ip.to_string());
+
+    throttle(
+        SECOND_FACTOR_SCOPE,
+        &key,
+        client_ip.as_deref(),
+        settings,
+        pool,
+    )
+    .await?;
+
+    if verify_second_factor(user_id, code, pool).await? {
+        clear_failures(SECOND_FACTOR_SCOPE, &key, pool).await?;
+        return Ok(());
+    }
+    record_failure(
+        SECOND_FACTOR_SCOPE,
+        &key,
+        settings.max_failures_per_username,
+        settings,
+        pool,
+    )
+    .await?;
+    if let Some(client_ip) = &client_ip {
+        record_failure(
+            IP_SCOPE,
+            client_ip,
+            settings.max_failures_per_ip,
+            settings,
+            pool,
+        )
+        .await?;
+    }
+    Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+        "Invalid second factor."
+    )))
+}
+
+// 被锁定时返回 `AuthError::TooManyAttempts`，否则按失败次数延迟
+async fn throttle(
+    scope: &str,
+    key: &str,
+    client_ip: Option<&str>,
+    settings: &LoginThrottlingSettings,
+    pool: &PgPool,
+) -> Result<(), AuthError> {
+    match check_attempt(scope, key, client_ip, settings, pool).await? {
+        Throttle::LockedOut(retry_after) => {
+            tracing::info!(
+                scope,
+                key,
+                client_ip = ?client_ip,
+                retry_after_seconds = retry_after.as_secs(),
+                "Rejected a login attempt during a lockout"
+            );
+            Err(AuthError::TooManyAttempts(retry_after))
+        }
+        Throttle::Delay(delay) => {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn check_attempt(
+    scope: &str,
+    key: &str,
+    client_ip: Option<&str>,
+    settings: &LoginThrottlingSettings,
+    pool: &PgPool,
+) -> Result<Throttle, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(MAX(
+                CASE WHEN last_failed_at > now() - make_interval(secs => $3)
+                THEN failures ELSE 0 END
+            ), 0) AS "failures!",
+            EXTRACT(EPOCH FROM MAX(locked_until) - now())::float8 AS locked_for_seconds
+        FROM login_failures
+        WHERE (scope = $4 AND key = $1) OR (scope = $5 AND key = $2)
+        "#,
+        key,
+        client_ip,
+        settings.failure_window().as_secs_f64(),
+        scope,
+        IP_SCOPE,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the failed login attempts.")?;
+
+    match row.locked_for_seconds {
+        Some(seconds) if seconds > 0.0 => {
+            Ok(Throttle::LockedOut(Duration::from_secs_f64(seconds.ceil())))
+        }
+        _ => Ok(Throttle::Delay(delay_after(row.failures, settings))),
+    }
+}
+
+// 第一次失败之后延迟 `base_delay`，之后每次翻倍，最多 `max_delay`
+fn delay_after(failures: i32, settings: &LoginThrottlingSettings) -> Duration {
+    if failures <= 0 {
+        return Duration::ZERO;
+    }
+    let exponent = (failures - 1).min(31) as u32;
+    settings
+        .base_delay()
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(settings.max_delay())
+}
+
+async fn record_failure(
+    scope: &str,
+    key: &str,
+    max_failures: i32,
+    settings: &LoginThrottlingSettings,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let failures = sqlx::query!(
+        r#"
+        INSERT INTO login_failures (scope, key, failures, last_failed_at)
+        VALUES ($1, $2, 1, now())
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = CASE
+                WHEN login_failures.last_failed_at > now() - make_interval(secs => $3)
+                THEN login_failures.failures + 1
+                ELSE 1
+            END,
+            last_failed_at = now()
+        RETURNING failures
+        "#,
+        scope,
+        key,
+        settings.failure_window().as_secs_f64(),
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to record a failed login attempt.")?
+    .failures;
+
+    if failures >= max_failures {
+        // 锁定后计数清零，锁定结束时重新开始计算延迟
+        sqlx::query!(
+            r#"
+            UPDATE login_failures
+            SET failures = 0, locked_until = now() + make_interval(secs => $3)
+            WHERE scope = $1 AND key = $2
+            "#,
+            scope,
+            key,
+            settings.lockout().as_secs_f64(),
+        )
+        .execute(pool)
+        .await
+        .context("Failed to lock out after too many failed login attempts.")?;
+        tracing::warn!(
+            scope,
+            key,
+            failures,
+            lockout_seconds = settings.lockout().as_secs(),
+            "Locked out after too many failed login attempts"
+        );
+    }
+    Ok(())
+}
+
+async fn clear_failures(scope: &str, key: &str, pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM login_failures WHERE scope = $1 AND key = $2"#,
+        scope,
+        key
+    )
+    .execute(pool)
+    .await
+    .context("Failed to clear the failed login attempts.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::delay_after;
+    use crate::configuration::LoginThrottlingSettings;
+    use std::time::Duration;
+
+    fn settings() -> LoginThrottlingSettings {
+        LoginThrottlingSettings {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 50,
+            failure_window_seconds: 900,
+            lockout_seconds: 900,
+            base_delay_milliseconds: 250,
+            max_delay_milliseconds: 4000,
+        }
+    }
+
+    #[test]
+    fn there_is_no_delay_before_the_first_failure() {
+        assert_eq!(delay_after(0, &settings()), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_after_each_failure() {
+        assert_eq!(delay_after(1, &settings()), Duration::from_millis(250));
+        assert_eq!(delay_after(2, &settings()), Duration::from_millis(500));
+        assert_eq!(delay_after(3, &settings()), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        assert_eq!(delay_after(6, &settings()), Duration::from_millis(4000));
+        assert_eq!(
+            delay_after(i32::MAX, &settings()),
+            Duration::from_millis(4000)
+        );
+    }
+}
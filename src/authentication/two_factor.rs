@@ -0,0 +1,214 @@
+use anyhow::Context;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::distr::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret as TotpSecret, TOTP};
+use uuid::Uuid;
+
+const ISSUER: &str = "zero2prod";
+const N_RECOVERY_CODES: usize = 10;
+
+/// 生成一个新的 160 位 TOTP 密钥，以 base32 编码返回
+pub fn generate_totp_secret() -> String {
+    let mut secret = [0u8; 20];
+    thread_rng().fill(&mut secret);
+    TotpSecret::Raw(secret.to_vec()).to_encoded().to_string()
+}
+
+/// 按 RFC 6238 的默认参数（SHA-1、6 位、30 秒）构建 TOTP，
+/// 允许前后各一个时间步的误差，兼容常见的身份验证器应用
+pub fn totp(secret: &str, account_name: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = TotpSecret::Encoded(secret.to_string())
+        .to_bytes()
+        .context("The TOTP secret is not valid base32.")?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .context("Failed to build a TOTP.")
+}
+
+/// 以内联 SVG 的形式渲染 `otpauth://` URI 的二维码
+pub fn otpauth_qr_code(totp: &TOTP) -> Result<String, anyhow::Error> {
+    let code = QrCode::new(totp.get_url().as_bytes()).context("Failed to encode the QR code.")?;
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .take(10)
+        .collect()
+}
+
+#[tracing::instrument(name = "Get TOTP secret", skip(pool))]
+pub async fn get_totp_secret(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret of a user.")?;
+    Ok(row.totp_secret)
+}
+
+/// 保存密钥并生成一组新的恢复码，旧的恢复码全部作废
+///
+/// 恢复码只在这里以明文返回一次，数据库中只保存摘要。
+#[tracing::instrument(name = "Enable two-factor authentication", skip(secret, pool))]
+pub async fn enable_two_factor(
+    user_id: Uuid,
+    secret: &str,
+    pool: &PgPool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let recovery_codes: Vec<String> = std::iter::repeat_with(generate_recovery_code)
+        .take(N_RECOVERY_CODES)
+        .collect();
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| hash_recovery_code(c))
+        .collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"UPDATE users SET totp_secret = $2, totp_last_used_step = NULL WHERE user_id = $1"#,
+        user_id,
+        secret
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the TOTP secret.")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the old recovery codes.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &code_hashes
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two-factor authentication.")?;
+    Ok(recovery_codes)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(user_id: Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"UPDATE users SET totp_secret = NULL, totp_last_used_step = NULL WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove the TOTP secret.")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to disable two-factor authentication.")?;
+    Ok(())
+}
+
+/// 验证第二因素：6 位数字按 TOTP 校验，其余输入按恢复码处理并在成功时将其作废
+///
+/// 每个 TOTP 时间步只能使用一次，不晚于上次通过验证的时间步的验证码都会被拒绝。
+#[tracing::instrument(name = "Verify second factor", skip(code, pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let code = code.trim();
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let secret = match get_totp_secret(user_id, pool).await? {
+            Some(secret) => secret,
+            None => return Ok(false),
+        };
+        let step = match matching_step(&totp(&secret, "")?, code)? {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        let n_updated = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_used_step = $2
+            WHERE user_id = $1
+                AND (totp_last_used_step IS NULL OR totp_last_used_step < $2)
+            "#,
+            user_id,
+            step as i64
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the used TOTP time step.")?
+        .rows_affected();
+        return Ok(n_updated == 1);
+    }
+
+    let n_used = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_recovery_code(code)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to use a recovery code.")?
+    .rows_affected();
+    Ok(n_used == 1)
+}
+
+// 在允许的误差范围内找到与验证码匹配的时间步，从最新的开始
+fn matching_step(totp: &TOTP, code: &str) -> Result<Option<u64>, anyhow::Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("The system clock is set before the UNIX epoch.")?
+        .as_secs();
+    let current_step = now / totp.step;
+    let skew = u64::from(totp.skew);
+    // 不带误差的副本只校验给定的时间步，比较仍然是常数时间的
+    let mut exact = totp.clone();
+    exact.skew = 0;
+    Ok((current_step.saturating_sub(skew)..=current_step + skew)
+        .rev()
+        .find(|step| exact.check(code, step * totp.step)))
+}
@@ -4,7 +4,7 @@ use serde_aux::prelude::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
 use std::time::Duration;
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, FileTransport, PostmarkTransport, SmtpTransport};
 
 #[derive(Clone, serde::Deserialize)]
 pub struct Settings {
@@ -12,6 +12,10 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub issue_delivery: IssueDeliverySettings,
+    pub idempotency: IdempotencySettings,
+    pub login_throttling: LoginThrottlingSettings,
+    pub feed: FeedSettings,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -21,28 +25,84 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret:Secret<String>,
+    // 确认链接的有效期，过期后需要重新订阅以获取新链接
+    pub subscription_token_ttl_hours: u64,
+    // 重置密码链接的有效期
+    pub password_reset_token_ttl_minutes: u64,
+    // 邀请新用户时设置密码链接的有效期，受邀者不一定马上查看邮件，所以比重置密码长
+    pub invitation_token_ttl_hours: u64,
+}
+
+impl ApplicationSettings {
+    pub fn subscription_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.subscription_token_ttl_hours * 60 * 60)
+    }
+
+    pub fn password_reset_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.password_reset_token_ttl_minutes * 60)
+    }
+
+    pub fn invitation_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.invitation_token_ttl_hours * 60 * 60)
+    }
 }
 
 #[derive(Clone, serde::Deserialize)]
 pub struct EmailClientSettings {
-    pub base_url: String,
     pub sender_email: String,
-    pub authorization_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    pub transport: EmailTransportSettings,
+}
+
+// 用 `kind` 字段选择发送邮件的后端，切换服务商只需要修改配置
+#[derive(Clone, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum EmailTransportSettings {
+    Postmark {
+        base_url: String,
+        authorization_token: Secret<String>,
+    },
+    Smtp {
+        host: String,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        port: u16,
+        username: String,
+        password: Secret<String>,
+    },
+    // 把邮件写入本地目录，便于离线开发
+    File {
+        directory: String,
+    },
 }
 
 impl EmailClientSettings {
     pub fn client(self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address.");
         let timeout = self.timeout();
-        EmailClient::new(
-            self.base_url,
-            sender_email,
-            self.authorization_token,
-            timeout,
-        )
+        match self.transport {
+            EmailTransportSettings::Postmark {
+                base_url,
+                authorization_token,
+            } => EmailClient::new(
+                sender_email,
+                PostmarkTransport::new(base_url, authorization_token, timeout),
+            ),
+            EmailTransportSettings::Smtp {
+                host,
+                port,
+                username,
+                password,
+            } => EmailClient::new(
+                sender_email,
+                SmtpTransport::new(&host, port, username, password, timeout)
+                    .expect("Invalid SMTP settings."),
+            ),
+            EmailTransportSettings::File { directory } => EmailClient::new(
+                sender_email,
+                FileTransport::new(directory).expect("Failed to create the email directory."),
+            ),
+        }
     }
-    //
 
     pub fn timeout(&self) -> Duration {
         Duration::from_millis(self.timeout_milliseconds)
@@ -53,6 +113,96 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(Clone, serde::Deserialize)]
+pub struct IssueDeliverySettings {
+    // 投递失败的任务在进入死信表之前最多尝试的次数
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: i16,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_backoff_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_milliseconds: u64,
+    // worker 每次从队列中取出并批量发送的任务数
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: i64,
+}
+
+impl IssueDeliverySettings {
+    pub fn base_backoff(&self) -> Duration {
+        Duration::from_millis(self.base_backoff_milliseconds)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_milliseconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct IdempotencySettings {
+    // 超过保留时间的幂等键被视为新的请求，并由后台任务清理
+    pub retention_hours: u64,
+    pub cleanup_interval_seconds: u64,
+    // 重复请求等待同一个键的进行中请求完成的最长时间，超时返回 409
+    pub concurrent_request_timeout_milliseconds: u64,
+    // 每次清理最多删除的行数，避免长时间持有锁
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cleanup_batch_size: i64,
+}
+
+impl IdempotencySettings {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_hours * 60 * 60)
+    }
+
+    pub fn cleanup_interval(&self) -> Duration {
+        Duration::from_secs(self.cleanup_interval_seconds)
+    }
+
+    pub fn concurrent_request_timeout(&self) -> Duration {
+        Duration::from_millis(self.concurrent_request_timeout_milliseconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct LoginThrottlingSettings {
+    // 同一个用户名或同一个客户端 IP 连续失败达到次数后被临时锁定
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: i32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: i32,
+    // 距离上次失败超过这段时间后，失败次数重新计算
+    pub failure_window_seconds: u64,
+    pub lockout_seconds: u64,
+    // 每次失败后，下一次尝试的延迟翻倍，直到达到上限
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+}
+
+impl LoginThrottlingSettings {
+    pub fn failure_window(&self) -> Duration {
+        Duration::from_secs(self.failure_window_seconds)
+    }
+
+    pub fn lockout(&self) -> Duration {
+        Duration::from_secs(self.lockout_seconds)
+    }
+
+    pub fn base_delay(&self) -> Duration {
+        Duration::from_millis(self.base_delay_milliseconds)
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_milliseconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct FeedSettings {
+    // `/feed.atom` 和 `/feed.rss` 中最多包含的期刊数，按发布时间从新到旧
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub item_limit: i64,
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -62,6 +212,8 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    // 启动时是否自动执行 `migrations/` 中尚未应用的迁移
+    pub migrate_on_startup: bool,
 }
 
 impl DatabaseSettings {
@@ -0,0 +1,214 @@
+use crate::configuration::{IssueDeliverySettings, Settings};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::backoff;
+use crate::route::send_confirmation_email;
+use crate::startup::get_connection_pool;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
+use std::time::Duration;
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+pub enum ConfirmationOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+struct ConfirmationTask {
+    subscriber_id: Uuid,
+    n_retries: i16,
+    email: String,
+    name: String,
+    status: String,
+    // 管理员重新发送确认邮件时旧令牌会被删除，这里总是取最新的未使用令牌
+    subscription_token: Option<String>,
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    worker_loop(
+        connection_pool.await,
+        email_client,
+        configuration.application.base_url,
+        configuration.issue_delivery,
+    )
+    .await
+}
+
+/// 把导入的订阅者加入确认邮件队列，和订阅者在同一个事务中写入
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_confirmation_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+INSERT INTO confirmation_email_queue (subscriber_id, n_retries, execute_after)
+SELECT subscriber_id, 0, now() FROM UNNEST($1::uuid[]) AS subscriber_id
+ON CONFLICT DO NOTHING
+"#,
+        subscriber_ids
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+/// 从队列中取出一封到期的确认邮件并发送
+///
+/// 重试次数和退避时间沿用期刊投递的配置；用尽重试次数后放弃，
+/// 管理员可以在订阅者页面重新发送。
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty), err)]
+pub async fn try_send_confirmation_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    delivery_settings: &IssueDeliverySettings,
+) -> Result<ConfirmationOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        ConfirmationTask,
+        r#"
+SELECT
+q.subscriber_id,
+q.n_retries,
+s.email,
+s.name,
+s.status,
+(
+SELECT t.subscription_token
+FROM subscription_tokens t
+WHERE t.subscriber_id = q.subscriber_id AND t.used_at IS NULL
+ORDER BY t.created_at DESC
+LIMIT 1
+) AS subscription_token
+FROM confirmation_email_queue q
+JOIN subscriptions s ON s.id = q.subscriber_id
+WHERE q.execute_after <= now()
+FOR UPDATE OF q
+SKIP LOCKED
+LIMIT 1
+"#,
+    )
+    .fetch_optional(transaction.deref_mut())
+    .await?;
+    let task = match task {
+        Some(task) => task,
+        None => return Ok(ConfirmationOutcome::EmptyQueue),
+    };
+    Span::current().record("subscriber_id", &display(task.subscriber_id));
+
+    // 入队之后被手动确认、退订或重新签发过令牌的订阅者不再需要这封邮件
+    let subscription_token = match &task.subscription_token {
+        Some(token) if task.status == "pending_confirmation" => token.clone(),
+        _ => {
+            tracing::info!("Skipping a subscriber who is no longer pending confirmation.");
+            delete_task(&mut transaction, task.subscriber_id).await?;
+            transaction.commit().await?;
+            return Ok(ConfirmationOutcome::TaskCompleted);
+        }
+    };
+    let new_subscriber = match (
+        SubscriberEmail::parse(task.email.clone()),
+        SubscriberName::parse(task.name.clone()),
+    ) {
+        (Ok(email), Ok(name)) => NewSubscriber { email, name },
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!(
+            error.message = %e,
+            "Skipping a subscriber. Their stored contact details are invalid"
+            );
+            delete_task(&mut transaction, task.subscriber_id).await?;
+            transaction.commit().await?;
+            return Ok(ConfirmationOutcome::TaskCompleted);
+        }
+    };
+
+    match send_confirmation_email(email_client, new_subscriber, base_url, &subscription_token).await
+    {
+        Ok(()) => delete_task(&mut transaction, task.subscriber_id).await?,
+        Err(e) => {
+            let n_attempts = task.n_retries + 1;
+            if n_attempts >= delivery_settings.max_attempts {
+                tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a confirmation email after {} attempts. Giving up.",
+                n_attempts
+                );
+                delete_task(&mut transaction, task.subscriber_id).await?;
+            } else {
+                let delay = backoff(delivery_settings, task.n_retries);
+                tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a confirmation email. Retrying in {:?}.",
+                delay
+                );
+                reschedule_task(&mut transaction, task.subscriber_id, delay).await?;
+            }
+        }
+    }
+    transaction.commit().await?;
+    Ok(ConfirmationOutcome::TaskCompleted)
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn delete_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM confirmation_email_queue WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn reschedule_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let execute_after = Utc::now() + chrono::Duration::from_std(delay)?;
+    sqlx::query!(
+        r#"
+UPDATE confirmation_email_queue
+SET
+n_retries = n_retries + 1,
+execute_after = $2
+WHERE subscriber_id = $1
+"#,
+        subscriber_id,
+        execute_after
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    delivery_settings: IssueDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_send_confirmation_email(&pool, &email_client, &base_url, &delivery_settings).await
+        {
+            Ok(ConfirmationOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ConfirmationOutcome::TaskCompleted) => {}
+        }
+    }
+}
@@ -1,7 +1,15 @@
 mod subscriber_name;
 mod subscriber_email;
 mod new_subscriber;
+mod unsubscribe_token;
+mod scheduled_time;
+mod password_policy;
+mod list_slug;
 
 pub use subscriber_name::SubscriberName;
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
\ No newline at end of file
+pub use subscriber_email::SubscriberEmail;
+pub use unsubscribe_token::UnsubscribeToken;
+pub use scheduled_time::ScheduledTime;
+pub use password_policy::{PasswordPolicy, PasswordPolicyViolation};
+pub use list_slug::ListSlug;
\ No newline at end of file
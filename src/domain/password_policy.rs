@@ -0,0 +1,130 @@
+use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashSet;
+
+// 随程序一起打包的常见密码列表，比较时忽略大小写
+static COMMON_PASSWORDS: Lazy<HashSet<&'static str>> =
+    Lazy::new(|| include_str!("common_passwords.txt").lines().collect());
+
+/// 新密码必须满足的规则
+///
+/// 这里只检查密码本身，"不能与当前密码相同" 需要比对已存储的哈希，
+/// 由 `authentication` 模块在计算新哈希之前完成。
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordPolicyViolation {
+    TooShort(usize),
+    TooLong(usize),
+    Common,
+    ReusesCurrentPassword,
+}
+
+impl std::fmt::Display for PasswordPolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordPolicyViolation::TooShort(min_length) => write!(
+                f,
+                "The new password must be at least {} characters long.",
+                min_length
+            ),
+            PasswordPolicyViolation::TooLong(max_length) => write!(
+                f,
+                "The new password must be at most {} characters long.",
+                max_length
+            ),
+            PasswordPolicyViolation::Common => write!(
+                f,
+                "The new password is too common - please choose a less predictable one."
+            ),
+            PasswordPolicyViolation::ReusesCurrentPassword => write!(
+                f,
+                "The new password must be different from the current password."
+            ),
+        }
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 12,
+            max_length: 128,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// 返回密码违反的所有规则，为空表示密码可以使用
+    pub fn check(&self, password: &Secret<String>) -> Vec<PasswordPolicyViolation> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        let mut violations = Vec::new();
+        if length < self.min_length {
+            violations.push(PasswordPolicyViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            violations.push(PasswordPolicyViolation::TooLong(self.max_length));
+        }
+        if COMMON_PASSWORDS.contains(password.to_lowercase().as_str()) {
+            violations.push(PasswordPolicyViolation::Common);
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PasswordPolicy, PasswordPolicyViolation};
+    use secrecy::Secret;
+
+    fn check(password: &str) -> Vec<PasswordPolicyViolation> {
+        PasswordPolicy::default().check(&Secret::new(password.to_string()))
+    }
+
+    #[test]
+    fn a_long_uncommon_password_is_accepted() {
+        assert!(check("correct horse battery").is_empty());
+    }
+
+    #[test]
+    fn an_empty_password_is_rejected() {
+        assert_eq!(check(""), vec![PasswordPolicyViolation::TooShort(12)]);
+    }
+
+    #[test]
+    fn a_12_character_password_is_accepted() {
+        assert!(check(&"ä".repeat(12)).is_empty());
+    }
+
+    #[test]
+    fn a_129_character_password_is_rejected() {
+        assert_eq!(
+            check(&"a".repeat(129)),
+            vec![PasswordPolicyViolation::TooLong(128)]
+        );
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_regardless_of_case() {
+        assert_eq!(
+            check("PasswordPassword"),
+            vec![PasswordPolicyViolation::Common]
+        );
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        assert_eq!(
+            check("password"),
+            vec![
+                PasswordPolicyViolation::TooShort(12),
+                PasswordPolicyViolation::Common
+            ]
+        );
+    }
+}
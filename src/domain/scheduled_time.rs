@@ -0,0 +1,73 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// 期刊的计划发布时间，必须晚于当前时间
+///
+/// 接受 `<input type="datetime-local">` 提交的格式（按 UTC 解释）以及 RFC 3339。
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledTime(DateTime<Utc>);
+
+impl ScheduledTime {
+    pub fn parse(s: String) -> Result<ScheduledTime, String> {
+        let s = s.trim();
+        let time = DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M")
+                    .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+                    .map(|t| t.and_utc())
+            })
+            .map_err(|_| format!("{} is not a valid date and time.", s))?;
+
+        if time <= Utc::now() {
+            return Err(format!("{} is in the past.", s));
+        }
+        Ok(Self(time))
+    }
+}
+
+impl AsRef<DateTime<Utc>> for ScheduledTime {
+    fn as_ref(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ScheduledTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m-%d %H:%M UTC"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScheduledTime;
+    use chrono::{Duration, Utc};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn a_datetime_local_value_in_the_future_is_parsed_successfully() {
+        let time = (Utc::now() + Duration::days(1)).format("%Y-%m-%dT%H:%M");
+        assert_ok!(ScheduledTime::parse(time.to_string()));
+    }
+
+    #[test]
+    fn an_rfc3339_value_in_the_future_is_parsed_successfully() {
+        let time = (Utc::now() + Duration::days(1)).to_rfc3339();
+        assert_ok!(ScheduledTime::parse(time));
+    }
+
+    #[test]
+    fn a_time_in_the_past_is_rejected() {
+        let time = (Utc::now() - Duration::minutes(5)).to_rfc3339();
+        assert_err!(ScheduledTime::parse(time));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert_err!(ScheduledTime::parse("next tuesday".to_string()));
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(ScheduledTime::parse("".to_string()));
+    }
+}
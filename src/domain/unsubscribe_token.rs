@@ -0,0 +1,106 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+/// 退订链接中携带的签名令牌，格式为 `{subscriber_id}.{hex(hmac_tag)}`
+///
+/// 令牌不落库：只要 `HmacSecret` 不变，同一订阅者的令牌就保持不变。
+#[derive(Debug)]
+pub struct UnsubscribeToken {
+    subscriber_id: Uuid,
+    token: String,
+}
+
+impl UnsubscribeToken {
+    pub fn generate(subscriber_id: Uuid, secret: &Secret<String>) -> Self {
+        let tag = mac(subscriber_id, secret).finalize().into_bytes();
+        Self {
+            subscriber_id,
+            token: format!("{}.{}", subscriber_id, hex::encode(tag)),
+        }
+    }
+
+    /// 校验令牌的签名 - 签名不匹配的令牌一律拒绝
+    pub fn parse(s: String, secret: &Secret<String>) -> Result<UnsubscribeToken, String> {
+        let invalid = || format!("{} is not a valid unsubscribe token.", s);
+
+        let (subscriber_id, tag) = s.split_once('.').ok_or_else(invalid)?;
+        let subscriber_id = Uuid::parse_str(subscriber_id).map_err(|_| invalid())?;
+        let tag = hex::decode(tag).map_err(|_| invalid())?;
+        // `verify_slice` 以恒定时间比较标签
+        mac(subscriber_id, secret)
+            .verify_slice(&tag)
+            .map_err(|_| invalid())?;
+
+        Ok(Self {
+            subscriber_id,
+            token: s,
+        })
+    }
+
+    pub fn subscriber_id(&self) -> Uuid {
+        self.subscriber_id
+    }
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.token
+    }
+}
+
+// 加上用途前缀，避免与同一密钥签发的其他标签混用
+fn mac(subscriber_id: Uuid, secret: &Secret<String>) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(b"unsubscribe:");
+    mac.update(subscriber_id.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsubscribeToken;
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new(Uuid::new_v4().to_string())
+    }
+
+    #[test]
+    fn a_generated_token_is_parsed_successfully() {
+        let secret = secret();
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(subscriber_id, &secret);
+
+        let parsed = assert_ok!(UnsubscribeToken::parse(token.as_ref().to_string(), &secret));
+
+        assert_eq!(parsed.subscriber_id(), subscriber_id);
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = UnsubscribeToken::generate(Uuid::new_v4(), &secret());
+        assert_err!(UnsubscribeToken::parse(
+            token.as_ref().to_string(),
+            &secret()
+        ));
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_is_rejected() {
+        let secret = secret();
+        let token = UnsubscribeToken::generate(Uuid::new_v4(), &secret);
+        let (_, tag) = token.as_ref().split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4(), tag);
+        assert_err!(UnsubscribeToken::parse(forged, &secret));
+    }
+
+    #[test]
+    fn a_token_without_a_tag_is_rejected() {
+        let token = Uuid::new_v4().to_string();
+        assert_err!(UnsubscribeToken::parse(token, &secret()));
+    }
+}
@@ -1,29 +1,61 @@
 use crate::domain::SubscriberEmail;
-use reqwest::Client;
-use secrecy::{ExposeSecret, Secret};
-use std::time::Duration;
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::MultiPart;
+use std::sync::Arc;
+
+mod file;
+mod postmark;
+mod smtp;
+
+pub use file::FileTransport;
+pub use postmark::PostmarkTransport;
+pub use smtp::SmtpTransport;
+
+/// 发送邮件的后端
+///
+/// 订阅处理器和投递 worker 只通过 `EmailClient` 使用它，具体用哪个实现由
+/// `EmailClientSettings` 决定。
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), anyhow::Error>;
+
+    /// 一次发送多封邮件，按输入顺序返回每封邮件各自的结果
+    ///
+    /// 发送成功时附带服务商分配的邮件 ID（如果有的话）。
+    /// 默认实现逐封调用 `send`；支持批量接口的后端应当覆盖它。
+    async fn send_batch(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+    ) -> Vec<Result<Option<String>, anyhow::Error>> {
+        let mut outcomes = Vec::with_capacity(emails.len());
+        for email in emails {
+            outcomes.push(self.send(email).await.map(|_| None));
+        }
+        outcomes
+    }
+}
+
+/// 交给 `EmailTransport` 发送的一封邮件
+pub struct OutgoingEmail<'a> {
+    pub from: &'a SubscriberEmail,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    /// 纯文本版本，和 HTML 版本一起发送，由收件人的邮件客户端选择显示哪个
+    pub text_content: &'a str,
+    pub headers: &'a [EmailHeader],
+}
 
 pub struct EmailClient {
-    http_client: Client,
-    base_url: String,
     sender: SubscriberEmail,
-    authorization_token: Secret<String>,
+    transport: Arc<dyn EmailTransport>,
 }
 
 impl EmailClient {
-    pub fn new(
-        base_url: String,
-        sender: SubscriberEmail,
-        authorization_token: Secret<String>,
-        timeout: Duration,
-    ) -> Self {
-        let http_client = Client::builder().timeout(timeout).build().unwrap();
-
+    pub fn new(sender: SubscriberEmail, transport: impl EmailTransport + 'static) -> Self {
         Self {
-            http_client,
-            base_url,
             sender,
-            authorization_token,
+            transport: Arc::new(transport),
         }
     }
 
@@ -32,46 +64,97 @@ impl EmailClient {
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
-        message_stream: &str,
-    ) -> Result<(), reqwest::Error> {
-        let url = format!("{}/email", self.base_url);
-        let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
+        text_content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// 与 `send_email` 相同，但额外附带自定义邮件头（例如 `List-Unsubscribe`）
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error> {
+        let email = OutgoingEmail {
+            from: &self.sender,
+            to: recipient,
             subject,
             html_body: html_content,
-            message_stream,
+            text_content,
+            headers,
         };
-        let _builder = self
-            .http_client
-            .post(&url)
-            .header(
-                "X-Postmark-Server-Token",
-                self.authorization_token.expose_secret(),
-            )
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
+        self.transport.send(&email).await
+    }
+
+    /// 批量发送，返回值与 `emails` 一一对应，调用方只需要重试失败的那些
+    pub async fn send_batch(
+        &self,
+        emails: &[BatchEmail],
+    ) -> Vec<Result<Option<String>, anyhow::Error>> {
+        let emails: Vec<OutgoingEmail<'_>> = emails
+            .iter()
+            .map(|email| OutgoingEmail {
+                from: &self.sender,
+                to: &email.recipient,
+                subject: &email.subject,
+                html_body: &email.html_content,
+                text_content: &email.text_content,
+                headers: &email.headers,
+            })
+            .collect();
+        self.transport.send_batch(&emails).await
     }
 }
 
-#[derive(serde::Serialize)]
+/// 批量发送中的一封邮件，发件人由 `EmailClient` 统一填写
+pub struct BatchEmail {
+    pub recipient: SubscriberEmail,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<EmailHeader>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "PascalCase")]
-struct SendEmailRequest<'a> {
-    from: &'a str,
-    to: &'a str,
-    subject: &'a str,
-    html_body: &'a str,
-    message_stream: &'a str,
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl EmailHeader {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// 构建 SMTP 和文件后端共用的 MIME 邮件
+fn build_message(email: &OutgoingEmail<'_>) -> Result<lettre::Message, anyhow::Error> {
+    let mut builder = lettre::Message::builder()
+        .from(email.from.as_ref().parse()?)
+        .to(email.to.as_ref().parse()?)
+        .subject(email.subject);
+    for header in email.headers {
+        let name = HeaderName::new_from_ascii(header.name.clone())?;
+        builder = builder.raw_header(HeaderValue::new(name, header.value.clone()));
+    }
+    Ok(builder.multipart(MultiPart::alternative_plain_html(
+        email.text_content.to_string(),
+        email.html_body.to_string(),
+    ))?)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{BatchEmail, EmailClient, EmailHeader, PostmarkTransport};
     use claim::{assert_err, assert_ok};
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
@@ -99,13 +182,26 @@ mod tests {
     /// 获取 `EmailClient` 的测试实例。
     fn email_client(base_url: String) -> EmailClient {
         EmailClient::new(
-            base_url,
             email(),
-            Secret::new(Faker.fake()),
-            Duration::from_millis(200),
+            PostmarkTransport::new(
+                base_url,
+                Secret::new(Faker.fake()),
+                Duration::from_millis(200),
+            ),
         )
     }
 
+    /// 生成批量发送中的一封随机邮件
+    fn batch_email() -> BatchEmail {
+        BatchEmail {
+            recipient: email(),
+            subject: subject(),
+            html_content: content(),
+            text_content: content(),
+            headers: vec![],
+        }
+    }
+
     #[tokio::test]
     async fn send_email_sends_the_expected_request() {
         let mock_server = MockServer::start().await;
@@ -201,6 +297,84 @@ mod tests {
         assert_ok!(outcome);
     }
 
+    #[tokio::test]
+    async fn send_email_with_headers_includes_the_headers_in_the_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let headers = [EmailHeader::new(
+            "List-Unsubscribe",
+            "<https://example.com>",
+        )];
+        let outcome = email_client
+            .send_email_with_headers(&email(), &subject(), &content(), &content(), &headers)
+            .await;
+
+        assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["Headers"][0]["Name"], "List-Unsubscribe");
+        assert_eq!(body["Headers"][0]["Value"], "<https://example.com>");
+    }
+
+    #[tokio::test]
+    async fn send_batch_returns_the_outcome_of_each_email() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817" },
+                { "ErrorCode": 406, "Message": "Inactive recipient" },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client
+            .send_batch(&[batch_email(), batch_email()])
+            .await;
+
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(
+            outcomes[0].as_ref().unwrap().as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+        assert_err!(&outcomes[1]);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn send_batch_fails_every_email_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client
+            .send_batch(&[batch_email(), batch_email()])
+            .await;
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.is_err()));
+    }
+
     struct SendEmailBodyMatcher;
 
     impl wiremock::Match for SendEmailBodyMatcher {
@@ -215,7 +389,7 @@ mod tests {
                     && body.get("To").is_some()
                     && body.get("Subject").is_some()
                     && body.get("HtmlBody").is_some()
-                    && body.get("MessageStream").is_some()
+                    && body.get("TextBody").is_some()
             } else {
                 // 如果解析失败  不匹配请求
                 false
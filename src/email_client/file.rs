@@ -0,0 +1,78 @@
+use crate::email_client::{build_message, EmailTransport, OutgoingEmail};
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+/// 把邮件以 `.eml` 文件的形式写入本地目录，而不是真正发送出去
+///
+/// 用于本地开发：不需要任何邮件服务商就可以跑通整个流程。
+pub struct FileTransport {
+    mailer: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, std::io::Error> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            mailer: AsyncFileTransport::new(directory),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), anyhow::Error> {
+        let message = build_message(email)?;
+        let id = self.mailer.send(message).await?;
+        tracing::info!("Email written to {}.eml", id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, EmailHeader, FileTransport};
+    use claim::assert_ok;
+    use fake::faker::internet::en::SafeEmail;
+    use fake::Fake;
+    use uuid::Uuid;
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_writes_the_message_to_the_directory() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let email_client = EmailClient::new(email(), FileTransport::new(&directory).unwrap());
+
+        let headers = [EmailHeader::new(
+            "List-Unsubscribe",
+            "<https://example.com>",
+        )];
+        let outcome = email_client
+            .send_email_with_headers(
+                &email(),
+                "Newsletter title",
+                "<p>Newsletter body</p>",
+                "Newsletter body",
+                &headers,
+            )
+            .await;
+
+        assert_ok!(outcome);
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let message = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(message.contains("Subject: Newsletter title"));
+        assert!(message.contains("List-Unsubscribe: <https://example.com>"));
+        assert!(message.contains("Content-Type: multipart/alternative"));
+        assert!(message.contains("Content-Type: text/plain"));
+        assert!(message.contains("Content-Type: text/html"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use crate::email_client::{EmailHeader, EmailTransport, OutgoingEmail};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+// Postmark 单次批量请求最多接受 500 封邮件
+const MAX_BATCH_SIZE: usize = 500;
+
+/// 通过 Postmark 的 HTTP API 发送邮件
+pub struct PostmarkTransport {
+    http_client: Client,
+    base_url: String,
+    authorization_token: Secret<String>,
+}
+
+impl PostmarkTransport {
+    pub fn new(base_url: String, authorization_token: Secret<String>, timeout: Duration) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), anyhow::Error> {
+        let url = format!("{}/email", self.base_url);
+        let request_body = SendEmailRequest::new(email);
+        self.http_client
+            .post(&url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn send_batch(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+    ) -> Vec<Result<Option<String>, anyhow::Error>> {
+        let mut outcomes = Vec::with_capacity(emails.len());
+        for chunk in emails.chunks(MAX_BATCH_SIZE) {
+            match self.send_chunk(chunk).await {
+                Ok(chunk_outcomes) => outcomes.extend(chunk_outcomes),
+                // 整个请求失败时，这一批中的每封邮件都视为发送失败
+                Err(e) => outcomes.extend(chunk.iter().map(|_| Err(anyhow::anyhow!("{:#}", e)))),
+            }
+        }
+        outcomes
+    }
+}
+
+impl PostmarkTransport {
+    async fn send_chunk(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+    ) -> Result<Vec<Result<Option<String>, anyhow::Error>>, anyhow::Error> {
+        let url = format!("{}/email/batch", self.base_url);
+        let request_body: Vec<_> = emails.iter().map(SendEmailRequest::new).collect();
+        let responses: Vec<SendEmailResponse> = self
+            .http_client
+            .post(&url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if responses.len() != emails.len() {
+            anyhow::bail!(
+                "Postmark returned {} results for a batch of {} emails.",
+                responses.len(),
+                emails.len()
+            );
+        }
+
+        // 批量接口总是返回 200，每封邮件是否被接受要看各自的 `ErrorCode`
+        Ok(responses
+            .into_iter()
+            .map(|response| match response.error_code {
+                0 => Ok(response.message_id),
+                error_code => Err(anyhow::anyhow!(
+                    "{} (Postmark error code {})",
+                    response.message,
+                    error_code
+                )),
+            })
+            .collect())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader],
+}
+
+impl<'a> SendEmailRequest<'a> {
+    fn new(email: &OutgoingEmail<'a>) -> Self {
+        Self {
+            from: email.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: email.subject,
+            html_body: email.html_body,
+            text_body: email.text_content,
+            headers: email.headers,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailResponse {
+    error_code: i64,
+    message: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
@@ -0,0 +1,37 @@
+use crate::email_client::{build_message, EmailTransport, OutgoingEmail};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+/// 通过 SMTP 服务器发送邮件，连接建立后使用 STARTTLS 升级为加密连接
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        username: String,
+        password: Secret<String>,
+        timeout: Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let credentials = Credentials::new(username, password.expose_secret().clone());
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+            .port(port)
+            .credentials(credentials)
+            .timeout(Some(timeout))
+            .build();
+        Ok(Self { mailer })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), anyhow::Error> {
+        let message = build_message(email)?;
+        self.mailer.send(message).await?;
+        Ok(())
+    }
+}
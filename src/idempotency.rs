@@ -1,5 +1,7 @@
 pub mod key;
+mod cleanup;
 mod persistence;
 
+pub use cleanup::*;
 pub use key::*;
 pub use persistence::*;
\ No newline at end of file
@@ -0,0 +1,69 @@
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::startup::get_connection_pool;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{field::display, Span};
+
+pub enum CleanupOutcome {
+    BatchDeleted,
+    NothingExpired,
+}
+
+pub async fn run_idempotency_cleanup_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    cleanup_loop(connection_pool.await, configuration.idempotency).await
+}
+
+/// 删除一批超过保留时间的幂等键
+///
+/// 每次最多删除 `cleanup_batch_size` 行，并跳过正被其他事务锁住的行，
+/// 这样清理任务不会长时间阻塞正在处理中的请求。
+#[tracing::instrument(skip_all, fields(n_deleted=tracing::field::Empty), err)]
+pub async fn try_delete_expired_idempotency_keys(
+    pool: &PgPool,
+    settings: &IdempotencySettings,
+) -> Result<CleanupOutcome, anyhow::Error> {
+    let expired_before = Utc::now() - chrono::Duration::from_std(settings.retention())?;
+    let n_deleted = sqlx::query!(
+        r#"
+DELETE FROM idempotency
+WHERE (user_id, idempotency_key) IN (
+SELECT user_id, idempotency_key
+FROM idempotency
+WHERE created_at < $1
+FOR UPDATE
+SKIP LOCKED
+LIMIT $2
+)
+"#,
+        expired_before,
+        settings.cleanup_batch_size
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Span::current().record("n_deleted", &display(n_deleted));
+
+    if n_deleted == 0 {
+        Ok(CleanupOutcome::NothingExpired)
+    } else {
+        Ok(CleanupOutcome::BatchDeleted)
+    }
+}
+
+async fn cleanup_loop(pool: PgPool, settings: IdempotencySettings) -> Result<(), anyhow::Error> {
+    loop {
+        match try_delete_expired_idempotency_keys(&pool, &settings).await {
+            Ok(CleanupOutcome::NothingExpired) => {
+                tokio::time::sleep(settings.cleanup_interval()).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(CleanupOutcome::BatchDeleted) => {}
+        }
+    }
+}
@@ -1,23 +1,44 @@
 use crate::idempotency::IdempotencyKey::IdempotencyKey;
 use actix_web::body::to_bytes;
+use actix_web::http::header::RETRY_AFTER;
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
+use chrono::Utc;
 use sqlx::{PgPool, Postgres, Transaction};
 use std::ops::DerefMut;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSaveResponse(HttpResponse),
+    // 使用同一个键的另一个请求在超时之前仍未完成
+    RequestInProgress,
 }
 
+/// 超过 `retention` 的旧记录会被当作不存在：清空保存的响应并重新开始处理
+///
+/// 如果另一个使用同一个键的请求仍在处理中，插入语句会等待它的事务结束，
+/// 然后直接返回它保存的响应；等待超过 `concurrent_request_timeout` 时返回
+/// `NextAction::RequestInProgress`。
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    retention: Duration,
+    concurrent_request_timeout: Duration,
 ) -> Result<NextAction, anyhow::Error> {
+    let expired_before = Utc::now() - chrono::Duration::from_std(retention)?;
     let mut transaction = pool.begin().await?;
-    let n_inserted_rows = sqlx::query!(
+    // 这里必须保持默认的 read committed 隔离级别：在 repeatable read 下，
+    // 等待结束后看到的并发插入会直接导致序列化失败，而不是冲突
+    sqlx::query!(
+        "SELECT set_config('lock_timeout', $1, true)",
+        format!("{}ms", concurrent_request_timeout.as_millis())
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    let n_inserted_rows = match sqlx::query!(
         r#"
         INSERT INTO idempotency (
             user_id,
@@ -25,29 +46,55 @@ pub async fn try_processing(
             created_at
         )
         VALUES ($1, $2, now())
-        ON CONFLICT DO NOTHING
+        ON CONFLICT (user_id, idempotency_key) DO UPDATE
+        SET
+            created_at = EXCLUDED.created_at,
+            response_status_code = NULL,
+            response_headers = NULL,
+            response_body = NULL
+        WHERE idempotency.created_at < $3
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        expired_before
     )
     .execute(transaction.deref_mut())
-    .await?
-    .rows_affected();
-
-    sqlx::query!("SET TRANSACTION ISOLATION LEVEL repeatable read")
+    .await
+    {
+        Ok(r) => r.rows_affected(),
+        Err(e) if is_lock_timeout(&e) => return Ok(NextAction::RequestInProgress),
+        Err(e) => return Err(e.into()),
+    };
+    sqlx::query!("SET LOCAL lock_timeout TO DEFAULT")
         .execute(transaction.deref_mut())
         .await?;
 
     if n_inserted_rows > 0 {
         Ok(NextAction::StartProcessing(transaction))
     } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
-        Ok(NextAction::ReturnSaveResponse(saved_response))
+        match get_saved_response(pool, idempotency_key, user_id).await? {
+            Some(saved_response) => Ok(NextAction::ReturnSaveResponse(saved_response)),
+            None => Ok(NextAction::RequestInProgress),
+        }
+    }
+}
+
+// Postgres 的 `lock_not_available` 错误码
+fn is_lock_timeout(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => e.code().as_deref() == Some("55P03"),
+        _ => false,
     }
 }
 
+/// 告诉客户端同一个请求仍在处理中，稍后重试即可拿到保存的响应
+pub fn request_in_progress(retry_after: Duration) -> HttpResponse {
+    let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    HttpResponse::Conflict()
+        .insert_header((RETRY_AFTER, retry_after.to_string()))
+        .finish()
+}
+
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
 struct HeaderPairRecord {
@@ -69,7 +116,8 @@ pub async fn get_saved_response(
         FROM idempotency
         WHERE
             user_id = $1 AND
-            idempotency_key = $2
+            idempotency_key = $2 AND
+            response_status_code IS NOT NULL
         "#,
         user_id,
         idempotency_key.as_ref()
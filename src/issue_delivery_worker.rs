@@ -1,8 +1,12 @@
-use crate::configuration::Settings;
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::configuration::{IssueDeliverySettings, Settings};
+use crate::domain::{SubscriberEmail, UnsubscribeToken};
+use crate::email_client::{BatchEmail, EmailClient, EmailHeader};
 use crate::startup::get_connection_pool;
+use chrono::Utc;
+use rand::{thread_rng, Rng};
+use secrecy::Secret;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use std::ops::DerefMut;
 use std::time::Duration;
 use tracing::{field::display, Span};
@@ -22,7 +26,14 @@ struct NewsletterIssue {
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool.await, email_client).await
+    worker_loop(
+        connection_pool.await,
+        email_client,
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+        configuration.issue_delivery,
+    )
+    .await
 }
 
 #[tracing::instrument(skip_all)]
@@ -42,95 +53,270 @@ newsletter_issue_id = $1
     Ok(issue)
 }
 
-#[tracing::instrument(
-    skip_all,
-    fields(
-        newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
-    ),
-    err
-)]
+// 只有仍处于确认状态的订阅者才会收到邮件 - 入队之后退订的订阅者会被跳过
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber_ids(
+    pool: &PgPool,
+    emails: &[String],
+) -> Result<HashMap<String, Uuid>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+SELECT id, email
+FROM subscriptions
+WHERE
+email = ANY($1) AND
+status = 'confirmed'
+"#,
+        emails
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.email, r.id)).collect())
+}
+
+/// 每位收件人专属的退订链接
+pub fn unsubscribe_link(
+    base_url: &str,
+    subscriber_id: Uuid,
+    hmac_secret: &Secret<String>,
+) -> String {
+    let token = UnsubscribeToken::generate(subscriber_id, hmac_secret);
+    format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        base_url,
+        token.as_ref()
+    )
+}
+
+/// 期刊在公开归档中的页面，附在每封期刊邮件的开头
+pub fn view_in_browser_link(base_url: &str, issue_id: Uuid) -> String {
+    format!("{}/archive/{}", base_url, issue_id)
+}
+
+/// 期刊邮件的 HTML 正文：前后加上在浏览器中查看和退订的链接
+pub fn issue_email_html(
+    view_in_browser_link: &str,
+    html_content: &str,
+    unsubscribe_link: &str,
+) -> String {
+    format!(
+        "<p><a href=\"{}\">View in browser</a></p>{}<p><a href=\"{}\">Unsubscribe</a></p>",
+        view_in_browser_link, html_content, unsubscribe_link
+    )
+}
+
+// RFC 8058：邮件客户端可以直接向 `List-Unsubscribe` 中的地址发起 POST 完成一键退订
+fn unsubscribe_headers(unsubscribe_link: &str) -> [EmailHeader; 2] {
+    [
+        EmailHeader::new("List-Unsubscribe", format!("<{}>", unsubscribe_link)),
+        EmailHeader::new("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+    ]
+}
+
+/// 从队列中取出一批到期的任务，并通过 `EmailClient::send_batch` 一次性发送
+///
+/// 每个任务按各自的发送结果处理：成功的删除，失败的重新调度或移入死信表，
+/// 所以只有失败的收件人会被重试。
+#[tracing::instrument(skip_all, fields(n_tasks=tracing::field::Empty), err)]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    delivery_settings: &IssueDeliverySettings,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let tasks = dequeue_tasks(pool, delivery_settings.batch_size).await?;
+    if tasks.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (_transaction, _issue_id, _email) = task.unwrap();
-
-    if let Some((transaction, issue_id, email)) = dequeue_task(pool).await? {
-        Span::current()
-            .record("newsletter_issue_id", &display(issue_id))
-            .record("subscriber_email", &display(&email));
-        match SubscriberEmail::parse(email.clone()) {
-            Ok(email) => {
-                let issue = get_issue(pool, issue_id).await?;
-                if let Err(e) = email_client
-                    .send_email(
-                        &email,
-                        &issue.title,
-                        &issue.html_content,
-                        &issue.text_content,
-                    )
-                    .await
-                {
-                    tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. \
-                    Skipping.",
-                    );
-                }
-            }
+    let (mut transaction, tasks) = tasks.unwrap();
+    Span::current().record("n_tasks", &display(tasks.len()));
+
+    let subscriber_emails: Vec<String> = tasks.iter().map(|t| t.subscriber_email.clone()).collect();
+    let subscriber_ids = get_confirmed_subscriber_ids(pool, &subscriber_emails).await?;
+    let mut issues = HashMap::new();
+
+    let mut tasks_to_send = Vec::new();
+    let mut emails = Vec::new();
+    for task in tasks {
+        let email = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+            Ok(email) => email,
             Err(e) => {
                 tracing::error!(
                 error.cause_chain = ?e,
                 error.message = %e,
+                newsletter_issue_id = %task.newsletter_issue_id,
                 "Skipping a confirmed subscriber. \
                 Their stored contact details are invalid",
                 );
+                record_delivery(&mut transaction, &task, "skipped_invalid_address", None).await?;
+                delete_task(&mut transaction, &task).await?;
+                continue;
             }
+        };
+        let Some(subscriber_id) = subscriber_ids.get(&task.subscriber_email) else {
+            tracing::info!(
+                newsletter_issue_id = %task.newsletter_issue_id,
+                subscriber_email = %task.subscriber_email,
+                "Skipping a subscriber who is no longer confirmed."
+            );
+            record_delivery(&mut transaction, &task, "skipped_unsubscribed", None).await?;
+            delete_task(&mut transaction, &task).await?;
+            continue;
+        };
+        if !issues.contains_key(&task.newsletter_issue_id) {
+            let issue = get_issue(pool, task.newsletter_issue_id).await?;
+            issues.insert(task.newsletter_issue_id, issue);
         }
+        let issue = &issues[&task.newsletter_issue_id];
 
-        delete_task(transaction, issue_id, &email).await?;
+        let unsubscribe_link = unsubscribe_link(base_url, *subscriber_id, hmac_secret);
+        emails.push(BatchEmail {
+            recipient: email,
+            subject: issue.title.clone(),
+            html_content: issue_email_html(
+                &view_in_browser_link(base_url, task.newsletter_issue_id),
+                &issue.html_content,
+                &unsubscribe_link,
+            ),
+            text_content: issue.text_content.clone(),
+            headers: unsubscribe_headers(&unsubscribe_link).to_vec(),
+        });
+        tasks_to_send.push(task);
     }
+
+    let outcomes = email_client.send_batch(&emails).await;
+    for (task, outcome) in tasks_to_send.iter().zip(outcomes) {
+        match outcome {
+            Ok(provider_message_id) => {
+                record_delivery(
+                    &mut transaction,
+                    task,
+                    "sent",
+                    provider_message_id.as_deref(),
+                )
+                .await?;
+                delete_task(&mut transaction, task).await?;
+            }
+            Err(e) => handle_failed_delivery(&mut transaction, task, &e, delivery_settings).await?,
+        }
+    }
+    transaction.commit().await?;
     Ok(ExecutionOutcome::TaskCompleted)
 }
+
+// 还有剩余次数就按退避时间重新调度，否则移入死信表
+async fn handle_failed_delivery(
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
+    e: &anyhow::Error,
+    delivery_settings: &IssueDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    let n_attempts = task.n_retries + 1;
+    if n_attempts >= delivery_settings.max_attempts {
+        tracing::error!(
+        error.cause_chain = ?e,
+        error.message = %e,
+        newsletter_issue_id = %task.newsletter_issue_id,
+        subscriber_email = %task.subscriber_email,
+        "Failed to deliver issue to a confirmed subscriber after {} attempts. \
+        Moving it to the dead-letter table.",
+        n_attempts
+        );
+        move_task_to_failures(transaction, task, &e.to_string()).await
+    } else {
+        let delay = backoff(delivery_settings, task.n_retries);
+        tracing::warn!(
+        error.cause_chain = ?e,
+        error.message = %e,
+        newsletter_issue_id = %task.newsletter_issue_id,
+        subscriber_email = %task.subscriber_email,
+        "Failed to deliver issue to a confirmed subscriber. \
+        Retrying in {:?}.",
+        delay
+        );
+        reschedule_task(transaction, task, delay).await
+    }
+}
+
+/// 指数退避：第 n 次重试前等待 `base * 2^n`（不超过 `max`），
+/// 再随机抖动到 `[delay / 2, delay]`，避免大量失败任务在同一时刻重试
+pub(crate) fn backoff(delivery_settings: &IssueDeliverySettings, n_retries: i16) -> Duration {
+    let factor = 2u32.saturating_pow(n_retries.max(0) as u32);
+    let delay = delivery_settings
+        .base_backoff()
+        .saturating_mul(factor)
+        .min(delivery_settings.max_backoff());
+    let jitter = thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+    delay - Duration::from_millis(jitter)
+}
+
 type PgTransaction = Transaction<'static, Postgres>;
+
+/// 为期刊目标列表中每位已确认的订阅者创建一条投递任务
+///
+/// 同时属于多个目标列表的订阅者只会收到一封邮件。
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+INSERT INTO issue_delivery_queue (
+newsletter_issue_id,
+subscriber_email
+)
+SELECT DISTINCT $1::uuid, s.email
+FROM subscriptions s
+JOIN list_memberships m ON m.subscriber_id = s.id
+JOIN newsletter_issue_lists il ON il.list_id = m.list_id
+WHERE il.newsletter_issue_id = $1 AND s.status = 'confirmed' AND m.status = 'confirmed'
+"#,
+        newsletter_issue_id,
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+}
+
+// 一次最多锁住 `batch_size` 行，其他 worker 会跳过它们去处理剩下的任务
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
+async fn dequeue_tasks(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+    batch_size: i64,
+) -> Result<Option<(PgTransaction, Vec<DeliveryTask>)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let r = sqlx::query!(
+    let tasks = sqlx::query_as!(
+        DeliveryTask,
         r#"
-SELECT newsletter_issue_id, subscriber_email
+SELECT newsletter_issue_id, subscriber_email, n_retries
 FROM issue_delivery_queue
+WHERE execute_after <= now()
 FOR UPDATE
 SKIP LOCKED
-LIMIT 1
+LIMIT $1
 "#,
+        batch_size
     )
-    .fetch_optional(transaction.deref_mut())
+    .fetch_all(transaction.deref_mut())
     .await?;
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
+    if tasks.is_empty() {
+        return Ok(None);
     }
+    Ok(Some((transaction, tasks)))
 }
 
 #[tracing::instrument(skip_all)]
 async fn delete_task(
-    mut transaction: PgTransaction,
-    issue_id: Uuid,
-    email: &str,
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
 ) -> Result<(), anyhow::Error> {
     sqlx::query!(
         r#"
@@ -139,18 +325,126 @@ WHERE
 newsletter_issue_id = $1 AND
 subscriber_email = $2
 "#,
-        issue_id,
-        email
+        task.newsletter_issue_id,
+        task.subscriber_email
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let execute_after = Utc::now() + chrono::Duration::from_std(delay)?;
+    sqlx::query!(
+        r#"
+UPDATE issue_delivery_queue
+SET
+n_retries = n_retries + 1,
+execute_after = $3
+WHERE
+newsletter_issue_id = $1 AND
+subscriber_email = $2
+"#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        execute_after
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+// 放弃重试：把任务移入死信表，管理员可以在后台查看并重新入队
+#[tracing::instrument(skip_all)]
+async fn move_task_to_failures(
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
+    last_error: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+INSERT INTO issue_delivery_failures (
+newsletter_issue_id,
+subscriber_email,
+n_attempts,
+last_error,
+failed_at
+)
+VALUES ($1, $2, $3, $4, now())
+ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+SET
+n_attempts = EXCLUDED.n_attempts,
+last_error = EXCLUDED.last_error,
+failed_at = EXCLUDED.failed_at
+"#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        task.n_retries + 1,
+        last_error
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    record_delivery(transaction, task, "failed", None).await?;
+    delete_task(transaction, task).await
+}
+
+// 任务离开队列时记录它的最终结果，供后台按期刊统计投递情况
+// 被重新入队并最终发送成功的任务会覆盖之前的 `failed` 记录
+#[tracing::instrument(skip(transaction, task))]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
+    status: &str,
+    provider_message_id: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+INSERT INTO issue_deliveries (
+newsletter_issue_id,
+subscriber_email,
+status,
+provider_message_id,
+recorded_at
+)
+VALUES ($1, $2, $3, $4, now())
+ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+SET
+status = EXCLUDED.status,
+provider_message_id = EXCLUDED.provider_message_id,
+recorded_at = EXCLUDED.recorded_at
+"#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        status,
+        provider_message_id
     )
     .execute(transaction.deref_mut())
     .await?;
-    transaction.commit().await?;
     Ok(())
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    delivery_settings: IssueDeliverySettings,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(
+            &pool,
+            &email_client,
+            &base_url,
+            &hmac_secret,
+            &delivery_settings,
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -161,3 +455,38 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::backoff;
+    use crate::configuration::IssueDeliverySettings;
+    use std::time::Duration;
+
+    fn delivery_settings() -> IssueDeliverySettings {
+        IssueDeliverySettings {
+            max_attempts: 5,
+            base_backoff_milliseconds: 1000,
+            max_backoff_milliseconds: 10_000,
+            batch_size: 100,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter() {
+        let settings = delivery_settings();
+        for n_retries in 0..3 {
+            let delay = backoff(&settings, n_retries);
+            let upper_bound = Duration::from_millis(1000 * 2u64.pow(n_retries as u32));
+            assert!(delay <= upper_bound);
+            assert!(delay >= upper_bound / 2);
+        }
+    }
+
+    #[test]
+    fn backoff_never_exceeds_the_configured_maximum() {
+        let settings = delivery_settings();
+        for n_retries in [4, 10, 100, i16::MAX] {
+            assert!(backoff(&settings, n_retries) <= settings.max_backoff());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::configuration::Settings;
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::startup::get_connection_pool;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use std::time::Duration;
+use tracing::{field::display, Span};
+
+pub enum SchedulingOutcome {
+    IssuePublished,
+    NothingDue,
+}
+
+pub async fn run_scheduler_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    scheduler_loop(connection_pool.await).await
+}
+
+/// 取出一期已到计划时间的期刊，标记为已发布并填充投递队列
+///
+/// `FOR UPDATE SKIP LOCKED` 保证多个实例同时运行时每期期刊只会入队一次。
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty), err)]
+pub async fn try_publish_scheduled_issue(
+    pool: &PgPool,
+) -> Result<SchedulingOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let issue = sqlx::query!(
+        r#"
+SELECT newsletter_issue_id
+FROM newsletter_issues
+WHERE
+status = 'scheduled' AND
+scheduled_for <= now()
+FOR UPDATE
+SKIP LOCKED
+LIMIT 1
+"#,
+    )
+    .fetch_optional(transaction.deref_mut())
+    .await?;
+    let issue_id = match issue {
+        Some(issue) => issue.newsletter_issue_id,
+        None => return Ok(SchedulingOutcome::NothingDue),
+    };
+    Span::current().record("newsletter_issue_id", &display(issue_id));
+
+    sqlx::query!(
+        r#"
+UPDATE newsletter_issues
+SET published_at = now(), status = 'published', updated_at = now()
+WHERE newsletter_issue_id = $1
+"#,
+        issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    enqueue_delivery_tasks(&mut transaction, issue_id).await?;
+    transaction.commit().await?;
+    Ok(SchedulingOutcome::IssuePublished)
+}
+
+async fn scheduler_loop(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        match try_publish_scheduled_issue(&pool).await {
+            Ok(SchedulingOutcome::NothingDue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(SchedulingOutcome::IssuePublished) => {}
+        }
+    }
+}
@@ -8,4 +8,7 @@ pub mod authentication;
 pub mod session_state;
 pub mod utils;
 pub mod idempotency;
-pub mod issue_delivery_worker;
\ No newline at end of file
+pub mod issue_delivery_worker;
+pub mod confirmation_email_worker;
+pub mod issue_scheduler;
+pub mod subscriber_lists;
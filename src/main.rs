@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
 use zero2prod::configuration::get_configuration;
+use zero2prod::confirmation_email_worker;
+use zero2prod::idempotency::run_idempotency_cleanup_until_stopped;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
-use zero2prod::startup::Application;
+use zero2prod::issue_scheduler::run_scheduler_until_stopped;
+use zero2prod::startup::{get_connection_pool, run_migrations, Application};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 #[tokio::main]
@@ -13,13 +16,36 @@ async fn main() -> anyhow::Result<()> {
     // 如果不能读取配置  panic
     let configuration = get_configuration().expect("Failed to read configuration.");
 
+    // `zero2prod migrate`：只执行数据库迁移，然后退出
+    if let Some(command) = std::env::args().nth(1) {
+        if command != "migrate" {
+            anyhow::bail!(
+                "Unknown command `{}`. The only supported command is `migrate`.",
+                command
+            );
+        }
+        let connection_pool = get_connection_pool(&configuration.database).await;
+        run_migrations(&connection_pool).await?;
+        tracing::info!("Database migrations have been applied");
+        return Ok(());
+    }
+
     let application = Application::build(configuration.clone()).await?;
     println!("{}", &application.port());
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let confirmation_worker_task = tokio::spawn(
+        confirmation_email_worker::run_worker_until_stopped(configuration.clone()),
+    );
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(configuration.clone()));
+    let idempotency_cleanup_task =
+        tokio::spawn(run_idempotency_cleanup_until_stopped(configuration));
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = confirmation_worker_task => report_exit("Confirmation email worker", o),
+        o = scheduler_task => report_exit("Issue scheduler", o),
+        o = idempotency_cleanup_task => report_exit("Idempotency cleanup", o),
     };
 
     Ok(())
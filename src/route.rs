@@ -1,13 +1,19 @@
 pub mod admin;
+mod archive;
+mod feed;
 pub mod health_check;
 mod home;
 pub mod newsletters;
 pub mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
 
 pub use crate::route::admin::*;
 pub use crate::route::newsletters::publish_newsletter;
+pub use archive::*;
+pub use feed::*;
 pub use health_check::*;
 pub use home::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
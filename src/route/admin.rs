@@ -1,9 +1,21 @@
+mod api_tokens;
 mod dashboard;
+mod delivery_failures;
 mod password;
+mod lists;
 mod logout;
 pub mod newsletter;
+mod subscribers;
+mod two_factor;
+mod users;
 
+pub use api_tokens::*;
 pub use dashboard::admin_dashboard;
+pub use delivery_failures::*;
 pub use password::*;
+pub use lists::{create_list, list_subscriber_lists};
 pub use logout::log_out;
-pub use newsletter::*;
\ No newline at end of file
+pub use newsletter::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
\ No newline at end of file
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::list_api_tokens;
+pub use post::{create_api_token, revoke_api_token};
@@ -0,0 +1,102 @@
+use crate::authentication::{get_api_tokens, ApiTokenScope, UserId};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn list_api_tokens(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let tokens = get_api_tokens(**user_id, &pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for token in &tokens {
+        let (status, action_html) = match token.revoked_at {
+            Some(revoked_at) => (format!("revoked at {}", revoked_at), String::new()),
+            None => (
+                "active".to_string(),
+                format!(
+                    r#"<form action="/admin/api-tokens/{}/revoke" method="post">
+                    <button type="submit">Revoke</button>
+                </form>"#,
+                    token.api_token_id
+                ),
+            ),
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{name}</td>
+            <td><code>{prefix}...</code></td>
+            <td>{scope}</td>
+            <td>{created_at}</td>
+            <td>{last_used_at}</td>
+            <td>{status}</td>
+            <td>{action_html}</td>
+        </tr>"#,
+            name = encode_minimal(&token.name),
+            prefix = token.token_prefix,
+            scope = token.scope,
+            created_at = token.created_at,
+            last_used_at = token
+                .last_used_at
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "never".to_string()),
+        )
+        .unwrap();
+    }
+
+    let mut scope_options = String::new();
+    for scope in ApiTokenScope::ALL {
+        write!(scope_options, r#"<option value="{scope}">{scope}</option>"#).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API tokens</title>
+</head>
+<body>
+    {msg_html}
+    <p>API tokens are sent as <code>Authorization: Bearer &lt;token&gt;</code>.</p>
+    <table>
+        <tr>
+            <th>Name</th>
+            <th>Token</th>
+            <th>Scope</th>
+            <th>Created at</th>
+            <th>Last used at</th>
+            <th>Status</th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    <h2>Create a token</h2>
+    <form action="/admin/api-tokens" method="post">
+        <label>Name
+            <input type="text" placeholder="What will use this token?" name="name">
+        </label>
+        <label>Scope
+            <select name="scope">{scope_options}</select>
+        </label>
+        <button type="submit">Create</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
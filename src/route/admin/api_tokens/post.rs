@@ -0,0 +1,95 @@
+use crate::authentication::{self, ApiTokenScope, Role, UserId};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use htmlescape::encode_minimal;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    scope: String,
+}
+
+#[tracing::instrument(
+    name = "Create an API token",
+    skip_all,
+    fields(user_id=%*user_id, scope=%form.scope)
+)]
+pub async fn create_api_token(
+    form: web::Form<FormData>,
+    user_id: ReqData<UserId>,
+    role: ReqData<Role>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The token name cannot be empty.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    let scope = match ApiTokenScope::parse(&form.scope) {
+        Ok(scope) => scope,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+    };
+    if !scope.is_allowed_for(*role) {
+        FlashMessage::error(format!(
+            "Your role does not allow creating {} tokens.",
+            scope
+        ))
+        .send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+
+    let token = authentication::create_api_token(**user_id, name, scope, &pool)
+        .await
+        .map_err(e500)?;
+
+    // 令牌明文只展示这一次，所以直接渲染页面而不是重定向
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API tokens</title>
+</head>
+<body>
+    <p>The API token {name} has been created with the {scope} scope.</p>
+    <p>Copy it now - it will not be shown again.</p>
+    <p><code>{token}</code></p>
+    <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+</body>
+</html>"#,
+            name = encode_minimal(name),
+            token = token.expose_secret(),
+        )))
+}
+
+#[tracing::instrument(
+    name = "Revoke an API token",
+    skip_all,
+    fields(user_id=%*user_id, api_token_id=%*api_token_id)
+)]
+pub async fn revoke_api_token(
+    api_token_id: web::Path<Uuid>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let revoked = authentication::revoke_api_token(**user_id, *api_token_id, &pool)
+        .await
+        .map_err(e500)?;
+    if revoked {
+        FlashMessage::info("The API token has been revoked.").send();
+    } else {
+        FlashMessage::error("The API token does not exist or has already been revoked.").send();
+    }
+    Ok(see_other("/admin/api-tokens"))
+}
@@ -1,6 +1,8 @@
+use crate::authentication::Role;
 use crate::session_state::TypedSession;
 use crate::utils::e500;
 use actix_web::http::header::{ContentType, LOCATION};
+use actix_web::web::ReqData;
 use actix_web::{web, HttpResponse};
 use anyhow::Context;
 use sqlx::PgPool;
@@ -9,6 +11,7 @@ use uuid::Uuid;
 pub async fn admin_dashboard(
     session: TypedSession,
     pool: web::Data<PgPool>,
+    role: ReqData<Role>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
         get_username(user_id, &pool).await.map_err(e500)?
@@ -18,6 +21,12 @@ pub async fn admin_dashboard(
             .finish());
     };
 
+    let users_link = if role.can_manage_users() {
+        r#"<li><a href="/admin/users">Manage users</a></li>"#
+    } else {
+        ""
+    };
+
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -29,18 +38,27 @@ pub async fn admin_dashboard(
 </head>
 <body>
     <p>Welcome {username}!</p>
+    <p>Your role: {role}</p>
     <p>Available actions:</p>
     <ol>
         <li><a href="/admin/password">Change password</a></li>
+        <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+        <li><a href="/admin/api-tokens">API tokens</a></li>
         <li>
           <form name="logoutForm" action="/admin/logout" method="post">
             <input type="submit" value="Logout">
           </form>
         </li>
         <li><a href="/admin/newsletters">Publish newsletters</a></li>
+        <li><a href="/admin/newsletters/history">Newsletter history</a></li>
+        <li><a href="/admin/delivery-failures">Failed deliveries</a></li>
+        <li><a href="/admin/subscribers">Subscribers</a></li>
+        <li><a href="/admin/lists">Subscriber lists</a></li>
+        {users_link}
     </ol>
 </body>
 </html>"#,
+            role = *role,
         )))
 }
 
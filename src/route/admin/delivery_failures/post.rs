@@ -0,0 +1,100 @@
+use crate::authentication::UserId;
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+}
+
+#[tracing::instrument(
+    name = "Re-queue a failed delivery",
+    skip_all,
+    fields(user_id=%*user_id, newsletter_issue_id=%form.newsletter_issue_id)
+)]
+pub async fn requeue_delivery_failure(
+    form: web::Form<FormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let outcome = requeue_failure(&pool, form.newsletter_issue_id, &form.subscriber_email)
+        .await
+        .context("Failed to re-queue a failed delivery")
+        .map_err(e500)?;
+
+    match outcome {
+        RequeueOutcome::NotFound => {
+            FlashMessage::error("The failed delivery no longer exists.").send()
+        }
+        RequeueOutcome::AlreadyQueued => {
+            FlashMessage::info("The delivery is already queued.").send()
+        }
+        RequeueOutcome::Requeued => FlashMessage::info("The delivery has been re-queued.").send(),
+    }
+    Ok(see_other("/admin/delivery-failures"))
+}
+
+enum RequeueOutcome {
+    Requeued,
+    // 队列中已经有同一个收件人的任务，死信记录照样删除
+    AlreadyQueued,
+    NotFound,
+}
+
+// 删除死信记录、清除 `failed` 投递记录与重新入队放在同一条语句中完成，要么都成功要么都不生效
+#[tracing::instrument(skip(pool))]
+async fn requeue_failure(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+) -> Result<RequeueOutcome, sqlx::Error> {
+    let r = sqlx::query!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM issue_delivery_failures
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2
+            RETURNING newsletter_issue_id, subscriber_email
+        ), cleared AS (
+            DELETE FROM issue_deliveries
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2 AND
+                status = 'failed'
+        ), inserted AS (
+            INSERT INTO issue_delivery_queue (
+                newsletter_issue_id,
+                subscriber_email,
+                n_retries,
+                execute_after
+            )
+            SELECT newsletter_issue_id, subscriber_email, 0, now()
+            FROM requeued
+            ON CONFLICT DO NOTHING
+            RETURNING newsletter_issue_id
+        )
+        SELECT
+            (SELECT count(*) FROM requeued) AS "n_deleted!",
+            (SELECT count(*) FROM inserted) AS "n_inserted!"
+        "#,
+        newsletter_issue_id,
+        subscriber_email
+    )
+    .fetch_one(pool)
+    .await?;
+    let outcome = if r.n_deleted == 0 {
+        RequeueOutcome::NotFound
+    } else if r.n_inserted == 0 {
+        RequeueOutcome::AlreadyQueued
+    } else {
+        RequeueOutcome::Requeued
+    };
+    Ok(outcome)
+}
@@ -0,0 +1,136 @@
+use crate::domain::ListSlug;
+use crate::subscriber_lists::create_subscriber_list;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+struct ListOverview {
+    slug: String,
+    name: String,
+    confirmed_subscribers: i64,
+}
+
+pub async fn list_subscriber_lists(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let lists = get_list_overviews(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for list in &lists {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{name}</td>
+            <td><code>{slug}</code></td>
+            <td>{confirmed_subscribers}</td>
+        </tr>"#,
+            name = encode_minimal(&list.name),
+            slug = list.slug,
+            confirmed_subscribers = list.confirmed_subscribers,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber lists</title>
+</head>
+<body>
+    {msg_html}
+    <p>Subscribers join a list by sending its slug in the <code>list</code> field of
+    <code>POST /subscriptions</code>.</p>
+    <table>
+        <tr>
+            <th>Name</th>
+            <th>Slug</th>
+            <th>Confirmed subscribers</th>
+        </tr>
+        {rows_html}
+    </table>
+    <h2>Create a list</h2>
+    <form action="/admin/lists" method="post">
+        <label>Name
+            <input type="text" placeholder="Enter the list name" name="name">
+        </label>
+        <label>Slug
+            <input type="text" placeholder="e.g. weekly-digest" name="slug">
+        </label>
+        <button type="submit">Create</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    slug: String,
+}
+
+#[tracing::instrument(name = "Create a subscriber list", skip_all, fields(slug=%form.slug))]
+pub async fn create_list(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.0.name.trim().to_string();
+    if name.is_empty() {
+        FlashMessage::error("The list name cannot be empty.").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    let slug = match ListSlug::parse(form.0.slug) {
+        Ok(slug) => slug,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+
+    if create_subscriber_list(&pool, &slug, &name)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info(format!("The list {} has been created.", slug)).send();
+    } else {
+        FlashMessage::error(format!("A list with the slug {} already exists.", slug)).send();
+    }
+    Ok(see_other("/admin/lists"))
+}
+
+#[tracing::instrument(name = "Get subscriber list overviews", skip(pool))]
+async fn get_list_overviews(pool: &PgPool) -> Result<Vec<ListOverview>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListOverview,
+        r#"
+        SELECT
+            l.slug,
+            l.name,
+            count(s.id) AS "confirmed_subscribers!"
+        FROM subscriber_lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.list_id AND m.status = 'confirmed'
+        LEFT JOIN subscriptions s ON s.id = m.subscriber_id AND s.status = 'confirmed'
+        GROUP BY l.list_id
+        ORDER BY l.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber lists.")?;
+    Ok(lists)
+}
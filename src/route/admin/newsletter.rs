@@ -1,5 +1,17 @@
+mod deliveries;
+mod drafts;
 mod get;
+mod history;
 mod post;
+mod preview;
+mod scheduled;
 
-pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
\ No newline at end of file
+pub use deliveries::{
+    get_delivery_counts, get_issue_title, newsletter_issue_deliveries, DeliveryCounts,
+};
+pub use drafts::{autosave_draft, create_draft, delete_draft, edit_draft, list_drafts, save_draft};
+pub use get::{publish_newsletter_form, render_issue_form, IssueDraft};
+pub use history::newsletter_history;
+pub use post::{insert_newsletter_issue, publish_newsletter, store_issue_lists, target_list_slugs};
+pub use preview::{preview_newsletter, send_test_newsletter};
+pub use scheduled::{cancel_scheduled_newsletter, reschedule_newsletter, scheduled_newsletters};
@@ -0,0 +1,158 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// 页面上最多列出的投递记录数，完整的统计见顶部的计数
+const MAX_LISTED_DELIVERIES: i64 = 100;
+
+#[derive(serde::Serialize)]
+pub struct DeliveryCounts {
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    pub pending: i64,
+}
+
+struct Delivery {
+    subscriber_email: String,
+    status: String,
+    provider_message_id: Option<String>,
+    recorded_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Show newsletter issue deliveries", skip(pool))]
+pub async fn newsletter_issue_deliveries(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let title = match get_issue_title(&pool, issue_id).await.map_err(e500)? {
+        Some(title) => title,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let counts = get_delivery_counts(&pool, issue_id).await.map_err(e500)?;
+    let deliveries = get_recent_deliveries(&pool, issue_id).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for d in &deliveries {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{email}</td>
+            <td>{status}</td>
+            <td>{message_id}</td>
+            <td>{recorded_at}</td>
+        </tr>"#,
+            email = encode_minimal(&d.subscriber_email),
+            status = d.status,
+            message_id = encode_minimal(d.provider_message_id.as_deref().unwrap_or("")),
+            recorded_at = d.recorded_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter issue deliveries</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <ul>
+        <li>Sent: {sent}</li>
+        <li>Failed: {failed}</li>
+        <li>Pending: {pending}</li>
+        <li>Skipped: {skipped}</li>
+    </ul>
+    <p>Most recent deliveries:</p>
+    <table>
+        <tr>
+            <th>Subscriber</th>
+            <th>Status</th>
+            <th>Provider message id</th>
+            <th>Recorded at</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            title = encode_minimal(&title),
+            sent = counts.sent,
+            failed = counts.failed,
+            pending = counts.pending,
+            skipped = counts.skipped,
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_issue_title(pool: &PgPool, issue_id: Uuid) -> Result<Option<String>, anyhow::Error> {
+    let r = sqlx::query!(
+        r#"SELECT title FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(r.map(|r| r.title))
+}
+
+// 尚在队列中的任务（包括等待重试的）都算作 pending
+#[tracing::instrument(skip(pool))]
+pub async fn get_delivery_counts(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<DeliveryCounts, anyhow::Error> {
+    let counts = sqlx::query_as!(
+        DeliveryCounts,
+        r#"
+        SELECT
+            (SELECT count(*) FROM issue_deliveries
+             WHERE newsletter_issue_id = $1 AND status = 'sent') as "sent!",
+            (SELECT count(*) FROM issue_deliveries
+             WHERE newsletter_issue_id = $1 AND status = 'failed') as "failed!",
+            (SELECT count(*) FROM issue_deliveries
+             WHERE newsletter_issue_id = $1 AND status LIKE 'skipped%') as "skipped!",
+            (SELECT count(*) FROM issue_delivery_queue
+             WHERE newsletter_issue_id = $1) as "pending!"
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the deliveries of a newsletter issue.")?;
+    Ok(counts)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_recent_deliveries(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<Delivery>, anyhow::Error> {
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"
+        SELECT subscriber_email, status, provider_message_id, recorded_at
+        FROM issue_deliveries
+        WHERE newsletter_issue_id = $1
+        ORDER BY recorded_at DESC
+        LIMIT $2
+        "#,
+        issue_id,
+        MAX_LISTED_DELIVERIES
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the deliveries of a newsletter issue.")?;
+    Ok(deliveries)
+}
@@ -0,0 +1,394 @@
+use crate::authentication::UserId;
+use crate::route::admin::newsletter::{
+    render_issue_form, store_issue_lists, target_list_slugs, IssueDraft,
+};
+use crate::subscriber_lists::{resolve_list_ids, ListLookupError};
+use crate::utils::{e400, e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+/// 草稿表单和发布表单是同一个，这里只关心内容和目标列表，其余字段被忽略
+#[derive(serde::Deserialize)]
+pub struct DraftForm {
+    title: String,
+    html_content: String,
+    message_stream: String,
+    #[serde(flatten)]
+    fields: HashMap<String, String>,
+}
+
+struct DraftOverview {
+    newsletter_issue_id: Uuid,
+    title: String,
+    author: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Show newsletter drafts", skip_all)]
+pub async fn list_drafts(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let drafts = get_drafts(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for d in &drafts {
+        let title = if d.title.trim().is_empty() {
+            "(untitled)".to_string()
+        } else {
+            encode_minimal(&d.title)
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td><a href="/admin/newsletters/drafts/{id}">{title}</a></td>
+            <td>{author}</td>
+            <td>{updated_at}</td>
+            <td>
+                <form action="/admin/newsletters/drafts/{id}/delete" method="post">
+                    <button type="submit">Delete</button>
+                </form>
+            </td>
+        </tr>"#,
+            id = d.newsletter_issue_id,
+            author = encode_minimal(d.author.as_deref().unwrap_or("-")),
+            updated_at = d.updated_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter drafts</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Title</th>
+            <th>Author</th>
+            <th>Last saved</th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/newsletters">New issue</a></p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    name = "Create a newsletter draft",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn create_draft(
+    form: web::Form<DraftForm>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let DraftForm {
+        title,
+        html_content,
+        message_stream,
+        fields,
+    } = form.0;
+    let list_ids = draft_list_ids(&pool, fields).await?;
+    let draft_id = insert_draft(
+        &pool,
+        &title,
+        &message_stream,
+        &html_content,
+        &list_ids,
+        *user_id,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&format!(
+        "/admin/newsletters/drafts/{}",
+        draft_id
+    )))
+}
+
+#[tracing::instrument(name = "Edit a newsletter draft", skip(pool, flash_messages))]
+pub async fn edit_draft(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    match get_draft(&pool, *issue_id).await.map_err(e500)? {
+        Some(draft) => render_issue_form(&pool, &flash_messages, Some(&draft)).await,
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(
+    name = "Save a newsletter draft",
+    skip(form, pool),
+    fields(newsletter_issue_id=%*issue_id)
+)]
+pub async fn save_draft(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<DraftForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if update_draft(&pool, *issue_id, form.0).await? {
+        FlashMessage::info("The draft has been saved.").send();
+        Ok(see_other(&format!(
+            "/admin/newsletters/drafts/{}",
+            issue_id
+        )))
+    } else {
+        FlashMessage::error("The draft no longer exists or has already been published.").send();
+        Ok(see_other("/admin/newsletters/drafts"))
+    }
+}
+
+/// 编辑页面的脚本在后台调用，所以不跳转也不设置 flash 消息
+#[tracing::instrument(
+    name = "Autosave a newsletter draft",
+    skip(form, pool),
+    fields(newsletter_issue_id=%*issue_id)
+)]
+pub async fn autosave_draft(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<DraftForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if update_draft(&pool, *issue_id, form.0).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[tracing::instrument(name = "Delete a newsletter draft", skip(pool))]
+pub async fn delete_draft(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let is_draft = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        FOR UPDATE
+        "#,
+        *issue_id
+    )
+    .fetch_optional(transaction.deref_mut())
+    .await
+    .context("Failed to lock a newsletter draft")
+    .map_err(e500)?
+    .is_some();
+    if !is_draft {
+        FlashMessage::error("The draft no longer exists or has already been published.").send();
+        return Ok(see_other("/admin/newsletters/drafts"));
+    }
+    sqlx::query!(
+        "DELETE FROM newsletter_issue_lists WHERE newsletter_issue_id = $1",
+        *issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete the target lists of a newsletter draft")
+    .map_err(e500)?;
+    sqlx::query!(
+        "DELETE FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        *issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete a newsletter draft")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a newsletter draft")
+        .map_err(e500)?;
+
+    FlashMessage::info("The draft has been deleted.").send();
+    Ok(see_other("/admin/newsletters/drafts"))
+}
+
+// 复选框都来自现有的列表，对不上说明表单被篡改过
+async fn draft_list_ids(
+    pool: &PgPool,
+    fields: HashMap<String, String>,
+) -> Result<Vec<Uuid>, actix_web::Error> {
+    let slugs = target_list_slugs(fields).map_err(e400)?;
+    resolve_list_ids(pool, &slugs).await.map_err(|e| match e {
+        ListLookupError::UnexpectedError(e) => e500(e),
+        e => e400(e),
+    })
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_draft(
+    pool: &PgPool,
+    title: &str,
+    message_stream: &str,
+    html_content: &str,
+    list_ids: &[Uuid],
+    created_by: Uuid,
+) -> Result<Uuid, anyhow::Error> {
+    let draft_id = Uuid::new_v4();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            created_by,
+            status,
+            updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5, 'draft', now())
+        "#,
+        draft_id,
+        title,
+        message_stream,
+        html_content,
+        created_by
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to store a newsletter draft")?;
+    store_issue_lists(&mut transaction, draft_id, list_ids)
+        .await
+        .context("Failed to store the target lists of a newsletter draft")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a newsletter draft")?;
+    Ok(draft_id)
+}
+
+// 草稿不存在或已经发布时返回 `false`
+async fn update_draft(
+    pool: &PgPool,
+    draft_id: Uuid,
+    form: DraftForm,
+) -> Result<bool, actix_web::Error> {
+    let DraftForm {
+        title,
+        html_content,
+        message_stream,
+        fields,
+    } = form;
+    let list_ids = draft_list_ids(pool, fields).await?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2, text_content = $3, html_content = $4, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        draft_id,
+        title,
+        message_stream,
+        html_content
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to update a newsletter draft")
+    .map_err(e500)?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Ok(false);
+    }
+    store_issue_lists(&mut transaction, draft_id, &list_ids)
+        .await
+        .context("Failed to store the target lists of a newsletter draft")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a newsletter draft")
+        .map_err(e500)?;
+    Ok(true)
+}
+
+#[tracing::instrument(name = "Get a newsletter draft", skip(pool))]
+async fn get_draft(pool: &PgPool, draft_id: Uuid) -> Result<Option<IssueDraft>, anyhow::Error> {
+    let draft = sqlx::query_as!(
+        IssueDraft,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            ARRAY(
+                SELECT list_id FROM newsletter_issue_lists
+                WHERE newsletter_issue_id = $1
+            ) AS "list_ids!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        draft_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a newsletter draft.")?;
+    Ok(draft)
+}
+
+#[tracing::instrument(name = "Get newsletter drafts", skip(pool))]
+async fn get_drafts(pool: &PgPool) -> Result<Vec<DraftOverview>, anyhow::Error> {
+    let drafts = sqlx::query_as!(
+        DraftOverview,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            u.username AS "author?",
+            i.updated_at
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.created_by
+        WHERE i.status = 'draft'
+        ORDER BY i.updated_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve newsletter drafts.")?;
+    Ok(drafts)
+}
@@ -1,17 +1,82 @@
+use crate::subscriber_lists::{get_subscriber_lists, DEFAULT_LIST_SLUG};
+use crate::utils::e500;
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
+
+/// 编辑草稿时预先填入表单的内容
+pub struct IssueDraft {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub list_ids: Vec<Uuid>,
+}
 
 pub async fn publish_newsletter_form(
+    pool: web::Data<PgPool>,
     flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render_issue_form(&pool, &flash_messages, None).await
+}
+
+/// 新期刊和草稿共用的表单，各个按钮通过 `formaction` 提交到不同的路由
+pub async fn render_issue_form(
+    pool: &PgPool,
+    flash_messages: &IncomingFlashMessages,
+    draft: Option<&IssueDraft>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
 
-    let idempotency_key = uuid::Uuid::new_v4();
+    let mut lists_html = String::new();
+    for list in get_subscriber_lists(pool).await.map_err(e500)? {
+        let checked = match draft {
+            Some(draft) => draft.list_ids.contains(&list.list_id),
+            None => list.slug == DEFAULT_LIST_SLUG,
+        };
+        writeln!(
+            lists_html,
+            r#"<label><input type="checkbox" name="list_{slug}"{checked}> {name}</label><br>"#,
+            slug = list.slug,
+            name = encode_minimal(&list.name),
+            checked = if checked { " checked" } else { "" },
+        )
+        .unwrap();
+    }
+
+    let (heading, draft_html, save_action, autosave_html) = match draft {
+        Some(draft) => (
+            "Edit draft",
+            format!(
+                r#"<input hidden type="text" name="draft_id" value="{}">"#,
+                draft.newsletter_issue_id
+            ),
+            format!("/admin/newsletters/drafts/{}", draft.newsletter_issue_id),
+            autosave_script(draft.newsletter_issue_id),
+        ),
+        None => (
+            "Publish a newsletter issue",
+            String::new(),
+            "/admin/newsletters/drafts".to_string(),
+            "<p>Save the issue as a draft to turn on autosave.</p>".to_string(),
+        ),
+    };
+    let title = draft.map(|d| encode_minimal(&d.title)).unwrap_or_default();
+    let html_content = draft
+        .map(|d| encode_minimal(&d.html_content))
+        .unwrap_or_default();
+    let text_content = draft
+        .map(|d| encode_minimal(&d.text_content))
+        .unwrap_or_default();
+
+    let idempotency_key = Uuid::new_v4();
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -23,12 +88,14 @@ pub async fn publish_newsletter_form(
 </head>
 <body>
     {msg_html}
-    <form action="/admin/newsletters" method="post">
+    <h1>{heading}</h1>
+    <form id="issue-form" action="/admin/newsletters" method="post">
         <label>Title:<br>
             <input
                 type="text"
                 placeholder="Enter the issue title"
                 name="title"
+                value="{title}"
             >
         </label>
         <br>
@@ -38,7 +105,7 @@ pub async fn publish_newsletter_form(
                 name="html_content"
                 rows="20"
                 cols="50"
-            ></textarea>
+            >{html_content}</textarea>
         </label>
         <br>
            <label>Plain text content:<br>
@@ -47,14 +114,60 @@ pub async fn publish_newsletter_form(
                 name="message_stream"
                 rows="20"
                 cols="50"
-            ></textarea>
+            >{text_content}</textarea>
+        </label>
+        <br>
+        <fieldset>
+            <legend>Send to (subscribers on several lists get a single email):</legend>
+            {lists_html}
+        </fieldset>
+        <br>
+        <label>Schedule for (UTC, leave empty to publish now):<br>
+            <input type="datetime-local" name="scheduled_for">
         </label>
         <br>
         <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
+        {draft_html}
         <button type="submit">Publish</button>
+        <button type="submit" formaction="{save_action}">Save draft</button>
+        <button type="submit" formaction="/admin/newsletters/preview" formtarget="_blank">Preview</button>
+        <button type="submit" formaction="/admin/newsletters/test" formtarget="_blank">Send test to me</button>
     </form>
+    {autosave_html}
+    <p><a href="/admin/newsletters/drafts">Drafts</a></p>
+    <p><a href="/admin/newsletters/scheduled">Scheduled issues</a></p>
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html>"#,
         )))
-}
\ No newline at end of file
+}
+
+// 内容有变化时每 30 秒把表单提交到 autosave 路由，不会离开当前页面
+fn autosave_script(draft_id: Uuid) -> String {
+    format!(
+        r#"<p id="autosave-status"></p>
+    <script>
+        (function () {{
+            const form = document.getElementById("issue-form");
+            const status = document.getElementById("autosave-status");
+            let saved = new URLSearchParams(new FormData(form)).toString();
+            setInterval(async function () {{
+                const current = new URLSearchParams(new FormData(form)).toString();
+                if (current === saved) {{
+                    return;
+                }}
+                const response = await fetch("/admin/newsletters/drafts/{draft_id}/autosave", {{
+                    method: "POST",
+                    body: new URLSearchParams(new FormData(form)),
+                }});
+                if (response.ok) {{
+                    saved = current;
+                    status.textContent = "Draft saved at " + new Date().toLocaleTimeString();
+                }} else {{
+                    status.textContent = "The draft could not be saved.";
+                }}
+            }}, 30000);
+        }})();
+    </script>"#
+    )
+}
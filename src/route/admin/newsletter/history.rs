@@ -0,0 +1,161 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 50;
+
+struct HistoryEntry {
+    newsletter_issue_id: Uuid,
+    title: String,
+    // 迁移之前创建的期刊没有作者
+    author: Option<String>,
+    status: String,
+    published_at: Option<DateTime<Utc>>,
+    scheduled_for: Option<DateTime<Utc>>,
+    sent: i64,
+    failed: i64,
+    skipped: i64,
+    pending: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    page: Option<i64>,
+}
+
+#[tracing::instrument(name = "Show newsletter history", skip(query, pool))]
+pub async fn newsletter_history(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let (entries, n_total) = get_history(&pool, page).await.map_err(e500)?;
+    let n_pages = ((n_total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+
+    let mut rows_html = String::new();
+    for e in &entries {
+        // 已发布的期刊显示发布时间，计划中的显示计划时间
+        let date = e
+            .published_at
+            .or(e.scheduled_for)
+            .map(|d| d.to_rfc3339())
+            .unwrap_or_default();
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td><a href="{link}">{title}</a></td>
+            <td>{author}</td>
+            <td>{status}</td>
+            <td>{date}</td>
+            <td>{sent} sent, {failed} failed, {skipped} skipped, {pending} pending</td>
+        </tr>"#,
+            link = if e.status == "draft" {
+                format!("/admin/newsletters/drafts/{}", e.newsletter_issue_id)
+            } else {
+                format!("/admin/newsletters/{}", e.newsletter_issue_id)
+            },
+            title = encode_minimal(&e.title),
+            author = encode_minimal(e.author.as_deref().unwrap_or("-")),
+            status = e.status,
+            sent = e.sent,
+            failed = e.failed,
+            skipped = e.skipped,
+            pending = e.pending,
+        )
+        .unwrap();
+    }
+
+    let mut pagination_html = format!("<p>Page {} of {} ({} issues)", page, n_pages, n_total);
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#" <a href="/admin/newsletters/history?page={}">Previous</a>"#,
+            page - 1,
+        )
+        .unwrap();
+    }
+    if page < n_pages {
+        write!(
+            pagination_html,
+            r#" <a href="/admin/newsletters/history?page={}">Next</a>"#,
+            page + 1,
+        )
+        .unwrap();
+    }
+    pagination_html.push_str("</p>");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter history</title>
+</head>
+<body>
+    <table>
+        <tr>
+            <th>Title</th>
+            <th>Author</th>
+            <th>Status</th>
+            <th>Date</th>
+            <th>Delivery progress</th>
+        </tr>
+        {rows_html}
+    </table>
+    {pagination_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get newsletter history", skip(pool))]
+async fn get_history(pool: &PgPool, page: i64) -> Result<(Vec<HistoryEntry>, i64), anyhow::Error> {
+    // 尚未发布的期刊排在最前面，其余按发布时间倒序
+    let entries = sqlx::query_as!(
+        HistoryEntry,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            u.username AS "author?",
+            i.status,
+            i.published_at,
+            i.scheduled_for,
+            (SELECT count(*) FROM issue_deliveries d
+             WHERE d.newsletter_issue_id = i.newsletter_issue_id
+                AND d.status = 'sent') AS "sent!",
+            (SELECT count(*) FROM issue_deliveries d
+             WHERE d.newsletter_issue_id = i.newsletter_issue_id
+                AND d.status = 'failed') AS "failed!",
+            (SELECT count(*) FROM issue_deliveries d
+             WHERE d.newsletter_issue_id = i.newsletter_issue_id
+                AND d.status LIKE 'skipped%') AS "skipped!",
+            (SELECT count(*) FROM issue_delivery_queue q
+             WHERE q.newsletter_issue_id = i.newsletter_issue_id) AS "pending!"
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.created_by
+        ORDER BY i.published_at DESC NULLS FIRST, i.updated_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        PAGE_SIZE,
+        (page - 1).saturating_mul(PAGE_SIZE),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the newsletter history.")?;
+    let n_total = sqlx::query!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count newsletter issues.")?
+        .count;
+    Ok((entries, n_total))
+}
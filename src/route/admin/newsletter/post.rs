@@ -1,13 +1,18 @@
 use crate::authentication::UserId;
-use crate::domain::SubscriberEmail;
+use crate::configuration::IdempotencySettings;
+use crate::domain::{ListSlug, ScheduledTime, SubscriberEmail};
 use crate::idempotency::IdempotencyKey::IdempotencyKey;
-use crate::idempotency::{ save_response, try_processing, NextAction};
+use crate::idempotency::{request_in_progress, save_response, try_processing, NextAction};
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::subscriber_lists::{default_list_slug, resolve_list_ids, ListLookupError};
 use crate::utils::{e400, e500, see_other};
 use actix_web::web::ReqData;
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use htmlescape::encode_minimal;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use std::ops::DerefMut;
 use uuid::Uuid;
 
@@ -17,38 +22,40 @@ pub struct FormData {
     html_content: String,
     message_stream: String,
     idempotency_key: String,
+    // 留空表示立即发布
+    scheduled_for: Option<String>,
+    // 从草稿发布时由编辑页面带上
+    draft_id: Option<Uuid>,
+    // 每个勾选的目标列表对应一个 `list_<slug>` 字段，一个都没有时发送到默认列表
+    #[serde(flatten)]
+    lists: HashMap<String, String>,
 }
 
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-INSERT INTO issue_delivery_queue (
-newsletter_issue_id,
-subscriber_email
-)
-SELECT $1, email
-FROM subscriptions
-WHERE status = 'confirmed'
-"#,
-        newsletter_issue_id,
-    )
-    .execute(transaction.deref_mut())
-    .await?;
-    Ok(())
+// 目标列表写入期刊记录，所以计划发布的期刊到期时也会发送到同样的列表
+pub fn target_list_slugs(fields: HashMap<String, String>) -> Result<Vec<ListSlug>, String> {
+    let mut slugs = fields
+        .into_keys()
+        .filter_map(|key| key.strip_prefix("list_").map(str::to_string))
+        .map(ListSlug::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if slugs.is_empty() {
+        slugs.push(default_list_slug());
+    }
+    Ok(slugs)
 }
 
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     message_stream: &str,
     html_content: &str,
+    scheduled_for: Option<&ScheduledTime>,
+    list_ids: &[Uuid],
+    created_by: Uuid,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
+    // 计划发布的期刊暂不设置 `published_at`，由调度器在到期时填写并改为 `published`
     sqlx::query!(
         r#"
 INSERT INTO newsletter_issues (
@@ -56,20 +63,100 @@ newsletter_issue_id,
 title,
 text_content,
 html_content,
-published_at
+published_at,
+scheduled_for,
+created_by,
+status,
+updated_at
+)
+VALUES (
+$1, $2, $3, $4,
+CASE WHEN $5::timestamptz IS NULL THEN now() END,
+$5,
+$6,
+CASE WHEN $5::timestamptz IS NULL THEN 'published' ELSE 'scheduled' END,
+now()
 )
-VALUES ($1, $2, $3, $4, now())
 "#,
         newsletter_issue_id,
         title,
         message_stream,
-        html_content
+        html_content,
+        scheduled_for.map(|t| *t.as_ref()),
+        created_by
     )
     .execute(transaction.deref_mut())
     .await?;
+    store_issue_lists(transaction, newsletter_issue_id, list_ids).await?;
     Ok(newsletter_issue_id)
 }
 
+/// 用 `list_ids` 替换期刊的目标列表，草稿每次保存都会重新写入
+#[tracing::instrument(skip(transaction))]
+pub async fn store_issue_lists(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM newsletter_issue_lists WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT $1, list_id FROM UNNEST($2::uuid[]) AS list_id
+        "#,
+        newsletter_issue_id,
+        list_ids
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+/// 把草稿改为已发布或计划发布的期刊，草稿不存在或已经发布过时返回 `None`
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%draft_id))]
+async fn publish_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    draft_id: Uuid,
+    title: &str,
+    message_stream: &str,
+    html_content: &str,
+    scheduled_for: Option<&ScheduledTime>,
+    list_ids: &[Uuid],
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let n_updated_rows = sqlx::query!(
+        r#"
+UPDATE newsletter_issues
+SET
+title = $2,
+text_content = $3,
+html_content = $4,
+published_at = CASE WHEN $5::timestamptz IS NULL THEN now() END,
+scheduled_for = $5,
+status = CASE WHEN $5::timestamptz IS NULL THEN 'published' ELSE 'scheduled' END,
+updated_at = now()
+WHERE newsletter_issue_id = $1 AND status = 'draft'
+"#,
+        draft_id,
+        title,
+        message_stream,
+        html_content,
+        scheduled_for.map(|t| *t.as_ref())
+    )
+    .execute(transaction.deref_mut())
+    .await?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Ok(None);
+    }
+    store_issue_lists(transaction, draft_id, list_ids).await?;
+    Ok(Some(draft_id))
+}
+
 #[tracing::instrument(
     name = "Publish a newsletter issue",
     skip_all,
@@ -79,6 +166,7 @@ pub async fn publish_newsletter(
     form: web::Form<FormData>,
     user_id: ReqData<UserId>,
     pool: web::Data<PgPool>,
+    idempotency_settings: web::Data<IdempotencySettings>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let FormData {
@@ -86,35 +174,121 @@ pub async fn publish_newsletter(
         html_content,
         message_stream,
         idempotency_key,
+        scheduled_for,
+        draft_id,
+        lists,
     } = form.0;
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
+    // 校验失败时回到提交表单的页面，草稿的内容不会丢失
+    let form_page = match draft_id {
+        Some(draft_id) => format!("/admin/newsletters/drafts/{}", draft_id),
+        None => "/admin/newsletters".to_string(),
+    };
+    let scheduled_for = match scheduled_for.filter(|s| !s.trim().is_empty()) {
+        Some(s) => match ScheduledTime::parse(s) {
+            Ok(scheduled_for) => Some(scheduled_for),
+            Err(e) => {
+                FlashMessage::error(encode_minimal(&e)).send();
+                return Ok(see_other(&form_page));
+            }
+        },
+        None => None,
+    };
+    let list_ids = match target_list_slugs(lists) {
+        Ok(slugs) => match resolve_list_ids(&pool, &slugs).await {
+            Ok(list_ids) => list_ids,
+            Err(ListLookupError::UnexpectedError(e)) => return Err(e500(e)),
+            Err(e) => {
+                FlashMessage::error(e.to_string()).send();
+                return Ok(see_other(&form_page));
+            }
+        },
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other(&form_page));
+        }
+    };
+    let mut transaction = match try_processing(
+        &pool,
+        &idempotency_key,
+        *user_id,
+        idempotency_settings.retention(),
+        idempotency_settings.concurrent_request_timeout(),
+    )
+    .await
+    .map_err(e500)?
     {
         NextAction::StartProcessing(t) => t,
         NextAction::ReturnSaveResponse(saved_response) => {
             success_message().send();
             return Ok(saved_response);
         }
+        NextAction::RequestInProgress => {
+            return Ok(request_in_progress(
+                idempotency_settings.concurrent_request_timeout(),
+            ));
+        }
     };
-    let issue_id =
-        insert_newsletter_issue(&mut transaction, &title, &message_stream, &html_content)
+    let issue_id = match draft_id {
+        Some(draft_id) => {
+            let issue_id = publish_draft(
+                &mut transaction,
+                draft_id,
+                &title,
+                &message_stream,
+                &html_content,
+                scheduled_for.as_ref(),
+                &list_ids,
+            )
             .await
-            .context("Failed to store newsletter issue details")
+            .context("Failed to publish a newsletter draft")
             .map_err(e500)?;
-
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+            match issue_id {
+                Some(issue_id) => issue_id,
+                // 丢弃事务，幂等键不会被保存
+                None => {
+                    FlashMessage::error(
+                        "The draft no longer exists or has already been published.",
+                    )
+                    .send();
+                    return Ok(see_other("/admin/newsletters/drafts"));
+                }
+            }
+        }
+        None => insert_newsletter_issue(
+            &mut transaction,
+            &title,
+            &message_stream,
+            &html_content,
+            scheduled_for.as_ref(),
+            &list_ids,
+            *user_id,
+        )
         .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+        .context("Failed to store newsletter issue details")
+        .map_err(e500)?,
+    };
+
+    if scheduled_for.is_none() {
+        enqueue_delivery_tasks(&mut transaction, issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
 
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, *user_id, response)
         .await
         .map_err(e500)?;
 
-    success_message().send();
+    match scheduled_for {
+        Some(scheduled_for) => FlashMessage::info(format!(
+            "The newsletter issue has been scheduled for {}.",
+            scheduled_for
+        ))
+        .send(),
+        None => success_message().send(),
+    }
 
     Ok(response)
 }
@@ -0,0 +1,123 @@
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::issue_email_html;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// 预览和测试邮件还没有归档页面和退订令牌，链接只是占位
+const PLACEHOLDER_LINK: &str = "#";
+
+/// 和发布表单是同一个，其余字段被忽略
+#[derive(serde::Deserialize)]
+pub struct PreviewForm {
+    title: String,
+    html_content: String,
+    message_stream: String,
+}
+
+/// HTML 版本放在沙盒化的 iframe 里渲染，脚本不会在管理后台中执行
+#[tracing::instrument(name = "Preview a newsletter issue", skip_all)]
+pub async fn preview_newsletter(form: web::Form<PreviewForm>) -> HttpResponse {
+    let html_body = issue_email_html(PLACEHOLDER_LINK, &form.html_content, PLACEHOLDER_LINK);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Preview</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <h2>HTML</h2>
+    <iframe sandbox srcdoc="{html_body}" width="800" height="600"></iframe>
+    <h2>Plain text</h2>
+    <pre>{text_body}</pre>
+</body>
+</html>"#,
+            title = encode_minimal(&form.title),
+            html_body = encode_minimal(&html_body),
+            text_body = encode_minimal(&form.message_stream),
+        ))
+}
+
+/// 只发给当前登录的用户，不经过投递队列，也不会留下投递记录
+#[tracing::instrument(
+    name = "Send a test newsletter email",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn send_test_newsletter(
+    form: web::Form<PreviewForm>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let recipient = match get_user_email(&pool, *user_id).await.map_err(e500)? {
+        Some(email) => email,
+        None => {
+            return Ok(test_email_page(
+                StatusCode::BAD_REQUEST,
+                "Your account has no valid email address, so no test email can be sent.",
+            ))
+        }
+    };
+    let html_body = issue_email_html(PLACEHOLDER_LINK, &form.html_content, PLACEHOLDER_LINK);
+    email_client
+        .send_email(
+            &recipient,
+            &format!("[Test] {}", form.title),
+            &html_body,
+            &form.message_stream,
+        )
+        .await
+        .context("Failed to send a test newsletter email")
+        .map_err(e500)?;
+    Ok(test_email_page(
+        StatusCode::OK,
+        &format!("A test email has been sent to {}.", recipient.as_ref()),
+    ))
+}
+
+// 按钮在新标签页中提交，直接返回结果页面而不是跳转，编辑中的内容不会丢失
+fn test_email_page(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Test email</title>
+</head>
+<body>
+    <p>{}</p>
+</body>
+</html>"#,
+            encode_minimal(message)
+        ))
+}
+
+#[tracing::instrument(name = "Get the email of the current user", skip(pool))]
+async fn get_user_email(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<SubscriberEmail>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT email FROM users WHERE user_id = $1"#, user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to retrieve the email of a user.")?;
+    Ok(row
+        .email
+        .and_then(|email| SubscriberEmail::parse(email).ok()))
+}
@@ -0,0 +1,227 @@
+use crate::authentication::UserId;
+use crate::domain::ScheduledTime;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    scheduled_for: DateTime<Utc>,
+}
+
+pub async fn scheduled_newsletters(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let issues = get_scheduled_issues(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for issue in &issues {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{title}</td>
+            <td>{scheduled_for}</td>
+            <td>
+                <form action="/admin/newsletters/scheduled/{issue_id}/reschedule" method="post">
+                    <input type="datetime-local" name="scheduled_for" value="{current}">
+                    <button type="submit">Reschedule</button>
+                </form>
+            </td>
+            <td>
+                <form action="/admin/newsletters/scheduled/{issue_id}/cancel" method="post">
+                    <button type="submit">Cancel</button>
+                </form>
+            </td>
+        </tr>"#,
+            title = encode_minimal(&issue.title),
+            scheduled_for = issue.scheduled_for.format("%Y-%m-%d %H:%M UTC"),
+            current = issue.scheduled_for.format("%Y-%m-%dT%H:%M"),
+            issue_id = issue.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Scheduled Newsletter Issues</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Title</th>
+            <th>Scheduled for</th>
+            <th></th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/newsletters">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    scheduled_for: String,
+}
+
+#[tracing::instrument(
+    name = "Reschedule a newsletter issue",
+    skip_all,
+    fields(user_id=%*user_id, newsletter_issue_id=%*issue_id)
+)]
+pub async fn reschedule_newsletter(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<RescheduleFormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let scheduled_for = match ScheduledTime::parse(form.0.scheduled_for) {
+        Ok(scheduled_for) => scheduled_for,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/newsletters/scheduled"));
+        }
+    };
+
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_for = $2, updated_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = 'scheduled'
+        "#,
+        *issue_id,
+        scheduled_for.as_ref()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to reschedule a newsletter issue")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows == 0 {
+        not_scheduled_message().send();
+    } else {
+        FlashMessage::info(format!(
+            "The newsletter issue has been rescheduled for {}.",
+            scheduled_for
+        ))
+        .send();
+    }
+    Ok(see_other("/admin/newsletters/scheduled"))
+}
+
+#[tracing::instrument(
+    name = "Cancel a scheduled newsletter issue",
+    skip_all,
+    fields(user_id=%*user_id, newsletter_issue_id=%*issue_id)
+)]
+pub async fn cancel_scheduled_newsletter(
+    issue_id: web::Path<Uuid>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // 调度器以 `FOR UPDATE` 锁住到期的期刊，所以这里要么在发布前删除成功，
+    // 要么等到发布完成后发现期刊已不再是 `scheduled` 而什么也不做
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let is_scheduled = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = 'scheduled'
+        FOR UPDATE
+        "#,
+        *issue_id
+    )
+    .fetch_optional(transaction.deref_mut())
+    .await
+    .context("Failed to lock a scheduled newsletter issue")
+    .map_err(e500)?
+    .is_some();
+    if !is_scheduled {
+        not_scheduled_message().send();
+        return Ok(see_other("/admin/newsletters/scheduled"));
+    }
+    // 目标列表引用了期刊，需要先删除
+    sqlx::query!(
+        r#"DELETE FROM newsletter_issue_lists WHERE newsletter_issue_id = $1"#,
+        *issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete the target lists of a scheduled newsletter issue")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        *issue_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to cancel a scheduled newsletter issue")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to cancel a newsletter issue")
+        .map_err(e500)?;
+
+    FlashMessage::info("The scheduled newsletter issue has been cancelled.").send();
+    Ok(see_other("/admin/newsletters/scheduled"))
+}
+
+fn not_scheduled_message() -> FlashMessage {
+    FlashMessage::error(
+        "The newsletter issue is no longer scheduled - it may already be going out.",
+    )
+}
+
+#[tracing::instrument(name = "Get scheduled newsletter issues", skip(pool))]
+async fn get_scheduled_issues(pool: &PgPool) -> Result<Vec<ScheduledIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ScheduledIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            scheduled_for as "scheduled_for!"
+        FROM newsletter_issues
+        WHERE
+            status = 'scheduled'
+        ORDER BY scheduled_for
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve scheduled newsletter issues.")?;
+    Ok(issues)
+}
@@ -1,8 +1,11 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
+use crate::authentication::{
+    validate_credentials, AuthError, Credentials, SetPasswordError, UserId,
+};
 use crate::route::admin::dashboard::get_username;
 use crate::utils::{e500, see_other};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
@@ -25,6 +28,7 @@ pub async fn change_password(
             "You entered two different new passwords - the field values must match.",
         )
         .send();
+        return Ok(see_other("/admin/password"));
     }
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
@@ -40,12 +44,35 @@ pub async fn change_password(
                 FlashMessage::error("The current password is incorrect.").send();
                 Ok(see_other("/admin/password"))
             }
-            AuthError::UnexpectedError(_) => Err(e500(e).into()),
+            AuthError::TooManyAttempts(_) | AuthError::UnexpectedError(_) => {
+                Err(e500(e).into())
+            }
         };
     }
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if let Err(e) =
+        crate::authentication::change_password(&mut transaction, *user_id, form.0.new_password)
+            .await
+    {
+        return match e {
+            SetPasswordError::PolicyViolations(violations) => {
+                for violation in violations {
+                    FlashMessage::error(violation.to_string()).send();
+                }
+                Ok(see_other("/admin/password"))
+            }
+            SetPasswordError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+    transaction
+        .commit()
         .await
+        .context("Failed to commit SQL transaction to change a password.")
         .map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
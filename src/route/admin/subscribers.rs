@@ -0,0 +1,12 @@
+mod export;
+mod get;
+mod import;
+mod post;
+
+pub use export::export_subscribers;
+pub use get::list_subscribers;
+pub use import::{import_subscribers, import_subscribers_form};
+pub use post::{
+    confirm_subscriber_manually, delete_subscriber, resend_confirmation_email,
+    unsubscribe_subscriber,
+};
@@ -0,0 +1,91 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// 每次只从数据库读取一批，导出大表时内存占用和连接占用时间都保持不变
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// 以 CSV 流的形式导出 `subscriptions` 表，表头和导入时使用的一致
+pub async fn export_subscribers(pool: web::Data<PgPool>) -> HttpResponse {
+    // 状态中的外层 `None` 表示已经导出完毕，内层是上一批最后一行的 id
+    let batches = futures_util::stream::unfold(Some(None), move |cursor| {
+        let pool = pool.clone();
+        async move {
+            let after = cursor?;
+            match export_batch(&pool, after).await {
+                Ok((chunk, last_id)) => Some((Ok(chunk), last_id.map(Some))),
+                Err(e) => {
+                    tracing::error!(error.cause_chain = ?e, "Failed to export subscribers");
+                    Some((Err(e), None))
+                }
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.csv".into())],
+        })
+        .streaming(batches)
+}
+
+// 返回这一批的 CSV 内容；这一批不满时已经是最后一批，不再返回游标
+async fn export_batch(
+    pool: &PgPool,
+    after: Option<Uuid>,
+) -> Result<(Bytes, Option<Uuid>), anyhow::Error> {
+    let subscribers = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE $1::uuid IS NULL OR id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        EXPORT_BATCH_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers to export.")?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    if after.is_none() {
+        writer.write_record(["email", "name", "status", "subscribed_at"])?;
+    }
+    for s in &subscribers {
+        writer.write_record([
+            s.email.as_str(),
+            s.name.as_str(),
+            s.status.as_str(),
+            s.subscribed_at.to_rfc3339().as_str(),
+        ])?;
+    }
+    let chunk = writer
+        .into_inner()
+        .context("Failed to write subscribers as CSV.")?;
+
+    let last_id = if subscribers.len() as i64 == EXPORT_BATCH_SIZE {
+        subscribers.last().map(|s| s.id)
+    } else {
+        None
+    };
+    Ok((Bytes::from(chunk), last_id))
+}
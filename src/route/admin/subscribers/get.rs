@@ -0,0 +1,303 @@
+use crate::utils::{e400, e500};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 50;
+const STATUSES: [&str; 3] = ["pending_confirmation", "confirmed", "unsubscribed"];
+
+struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// 查询参数，表单中留空的字段视为未设置
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    page: Option<i64>,
+    q: Option<String>,
+    status: Option<String>,
+    // YYYY-MM-DD（UTC），两端都包含在内
+    subscribed_from: Option<String>,
+    subscribed_to: Option<String>,
+}
+
+struct SubscriberFilter {
+    search: Option<String>,
+    status: Option<String>,
+    subscribed_from: Option<NaiveDate>,
+    subscribed_to: Option<NaiveDate>,
+}
+
+impl SubscriberFilter {
+    fn parse(query: &QueryParams) -> Result<SubscriberFilter, String> {
+        let non_empty = |s: &Option<String>| {
+            s.as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let status = non_empty(&query.status);
+        if let Some(status) = &status {
+            if !STATUSES.contains(&status.as_str()) {
+                return Err(format!("{} is not a valid subscriber status.", status));
+            }
+        }
+        let parse_date = |s: Option<String>| {
+            s.map(|s| {
+                NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+                    .map_err(|_| format!("{} is not a valid date, use YYYY-MM-DD.", s))
+            })
+            .transpose()
+        };
+        Ok(SubscriberFilter {
+            search: non_empty(&query.q),
+            status,
+            subscribed_from: parse_date(non_empty(&query.subscribed_from))?,
+            subscribed_to: parse_date(non_empty(&query.subscribed_to))?,
+        })
+    }
+
+    // `ILIKE` 的模式，搜索词中的通配符按字面匹配
+    fn search_pattern(&self) -> Option<String> {
+        self.search.as_ref().map(|s| {
+            let escaped = s
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+    }
+
+    // 翻页链接需要带上当前的筛选条件
+    fn query_string(&self, page: i64) -> String {
+        let mut query = format!("page={}", page);
+        let fields = [
+            ("q", self.search.clone()),
+            ("status", self.status.clone()),
+            (
+                "subscribed_from",
+                self.subscribed_from.map(|d| d.to_string()),
+            ),
+            ("subscribed_to", self.subscribed_to.map(|d| d.to_string())),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                write!(query, "&{}={}", key, urlencoding::encode(&value)).unwrap();
+            }
+        }
+        query
+    }
+}
+
+pub async fn list_subscribers(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let filter = SubscriberFilter::parse(&query).map_err(e400)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let (subscribers, n_total) = get_subscribers(&pool, &filter, page).await.map_err(e500)?;
+    let n_pages = ((n_total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+
+    let mut rows_html = String::new();
+    for s in &subscribers {
+        let mut actions_html = String::new();
+        if s.status == "pending_confirmation" {
+            write!(
+                actions_html,
+                r#"<form action="/admin/subscribers/{id}/confirm" method="post">
+                    <button type="submit">Confirm</button>
+                </form>
+                <form action="/admin/subscribers/{id}/resend-confirmation" method="post">
+                    <button type="submit">Resend confirmation email</button>
+                </form>"#,
+                id = s.id,
+            )
+            .unwrap();
+        }
+        if s.status != "unsubscribed" {
+            write!(
+                actions_html,
+                r#"<form action="/admin/subscribers/{id}/unsubscribe" method="post">
+                    <button type="submit">Unsubscribe</button>
+                </form>"#,
+                id = s.id,
+            )
+            .unwrap();
+        }
+        write!(
+            actions_html,
+            r#"<form action="/admin/subscribers/{id}/delete" method="post">
+                    <button type="submit">Delete</button>
+                </form>"#,
+            id = s.id,
+        )
+        .unwrap();
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{email}</td>
+            <td>{name}</td>
+            <td>{status}</td>
+            <td>{subscribed_at}</td>
+            <td>{actions_html}</td>
+        </tr>"#,
+            email = encode_minimal(&s.email),
+            name = encode_minimal(&s.name),
+            status = s.status,
+            subscribed_at = s.subscribed_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    let mut pagination_html = format!("<p>Page {} of {} ({} subscribers)", page, n_pages, n_total);
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#" <a href="/admin/subscribers?{}">Previous</a>"#,
+            encode_minimal(&filter.query_string(page - 1)),
+        )
+        .unwrap();
+    }
+    if page < n_pages {
+        write!(
+            pagination_html,
+            r#" <a href="/admin/subscribers?{}">Next</a>"#,
+            encode_minimal(&filter.query_string(page + 1)),
+        )
+        .unwrap();
+    }
+    pagination_html.push_str("</p>");
+
+    let mut status_options = String::from(r#"<option value="">any</option>"#);
+    for status in STATUSES {
+        write!(
+            status_options,
+            r#"<option value="{status}"{selected}>{status}</option>"#,
+            selected = if filter.status.as_deref() == Some(status) {
+                " selected"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <p><a href="/admin/subscribers/import">Import from CSV</a> |
+    <a href="/admin/subscribers/export">Export as CSV</a></p>
+    <form action="/admin/subscribers" method="get">
+        <label>Search
+            <input type="text" placeholder="Email or name" name="q" value="{search}">
+        </label>
+        <label>Status
+            <select name="status">{status_options}</select>
+        </label>
+        <label>Subscribed from
+            <input type="date" name="subscribed_from" value="{subscribed_from}">
+        </label>
+        <label>to
+            <input type="date" name="subscribed_to" value="{subscribed_to}">
+        </label>
+        <button type="submit">Filter</button>
+    </form>
+    <table>
+        <tr>
+            <th>Email</th>
+            <th>Name</th>
+            <th>Status</th>
+            <th>Subscribed at</th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    {pagination_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            search = encode_minimal(filter.search.as_deref().unwrap_or("")),
+            subscribed_from = filter
+                .subscribed_from
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            subscribed_to = filter
+                .subscribed_to
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+        )))
+}
+
+#[tracing::instrument(name = "Get subscribers", skip(pool, filter))]
+async fn get_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    page: i64,
+) -> Result<(Vec<Subscriber>, i64), anyhow::Error> {
+    let search_pattern = filter.search_pattern();
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1)
+            AND ($2::text IS NULL OR status = $2)
+            AND ($3::date IS NULL OR subscribed_at >= $3::date)
+            AND ($4::date IS NULL OR subscribed_at < $4::date + 1)
+        ORDER BY subscribed_at DESC, id
+        LIMIT $5 OFFSET $6
+        "#,
+        search_pattern,
+        filter.status,
+        filter.subscribed_from,
+        filter.subscribed_to,
+        PAGE_SIZE,
+        (page - 1).saturating_mul(PAGE_SIZE),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers.")?;
+    let n_total = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1)
+            AND ($2::text IS NULL OR status = $2)
+            AND ($3::date IS NULL OR subscribed_at >= $3::date)
+            AND ($4::date IS NULL OR subscribed_at < $4::date + 1)
+        "#,
+        search_pattern,
+        filter.status,
+        filter.subscribed_from,
+        filter.subscribed_to,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count subscribers.")?
+    .count;
+    Ok((subscribers, n_total))
+}
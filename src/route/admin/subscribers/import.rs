@@ -0,0 +1,412 @@
+use crate::confirmation_email_worker::enqueue_confirmation_emails;
+use crate::domain::{ListSlug, NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::route::generate_subscription_token;
+use crate::subscriber_lists::{
+    get_subscriber_lists, resolve_list_ids, ListLookupError, DEFAULT_LIST_SLUG,
+};
+use crate::utils::{e400, e500};
+use actix_multipart::form::tempfile::TempFile;
+use actix_multipart::form::text::Text;
+use actix_multipart::form::MultipartForm;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::io::Read;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+pub async fn import_subscribers_form(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut list_options = String::new();
+    for list in get_subscriber_lists(&pool).await.map_err(e500)? {
+        write!(
+            list_options,
+            r#"<option value="{slug}"{selected}>{name}</option>"#,
+            slug = list.slug,
+            name = encode_minimal(&list.name),
+            selected = if list.slug == DEFAULT_LIST_SLUG {
+                " selected"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    <p>Upload a CSV file with a header row containing an <code>email</code> and a
+    <code>name</code> column. Other columns are ignored.</p>
+    <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+        <label>CSV file
+            <input type="file" name="file" accept=".csv,text/csv">
+        </label>
+        <br>
+        <label>Add to list
+            <select name="list">{list_options}</select>
+        </label>
+        <br>
+        <label><input type="radio" name="mode" value="confirmed">
+            Import as confirmed (consent on file)</label>
+        <br>
+        <label><input type="radio" name="mode" value="send_confirmation" checked>
+            Send a confirmation email</label>
+        <br>
+        <label><input type="checkbox" name="dry_run" checked>
+            Dry run (only validate the file, nothing is saved)</label>
+        <br>
+        <button type="submit">Import</button>
+    </form>
+    <p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(MultipartForm)]
+pub struct ImportFormData {
+    file: TempFile,
+    mode: Text<String>,
+    list: Option<Text<String>>,
+    // 复选框只有勾选时才会提交
+    dry_run: Option<Text<String>>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ImportMode {
+    Confirmed,
+    SendConfirmation,
+}
+
+impl ImportMode {
+    fn parse(s: &str) -> Result<ImportMode, String> {
+        match s {
+            "confirmed" => Ok(ImportMode::Confirmed),
+            "send_confirmation" => Ok(ImportMode::SendConfirmation),
+            other => Err(format!("{} is not a valid import mode.", other)),
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self {
+            ImportMode::Confirmed => "confirmed",
+            ImportMode::SendConfirmation => "pending_confirmation",
+        }
+    }
+}
+
+struct ImportRow {
+    line: u64,
+    subscriber: NewSubscriber,
+}
+
+struct RowError {
+    line: u64,
+    email: String,
+    error: String,
+}
+
+#[tracing::instrument(name = "Import subscribers from CSV", skip_all)]
+pub async fn import_subscribers(
+    MultipartForm(form): MultipartForm<ImportFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mode = ImportMode::parse(&form.mode).map_err(e400)?;
+    let dry_run = form.dry_run.is_some();
+    let list_slug = form
+        .list
+        .map_or_else(|| DEFAULT_LIST_SLUG.to_string(), Text::into_inner);
+    let list_slug = ListSlug::parse(list_slug).map_err(e400)?;
+    let list_id = match resolve_list_ids(&pool, &[list_slug]).await {
+        Ok(list_ids) => list_ids[0],
+        Err(e @ ListLookupError::UnknownList(_)) => return Err(e400(e)),
+        Err(ListLookupError::UnexpectedError(e)) => return Err(e500(e)),
+    };
+
+    let file = form
+        .file
+        .file
+        .reopen()
+        .context("Failed to open the uploaded CSV file.")
+        .map_err(e500)?;
+    let (mut rows, mut errors) = web::block(move || read_rows(std::io::BufReader::new(file)))
+        .await
+        .map_err(e500)?
+        .map_err(e400)?;
+
+    let existing_emails = get_existing_emails(&pool, &rows).await.map_err(e500)?;
+    rows.retain(|row| {
+        let email = row.subscriber.email.as_ref();
+        if existing_emails.contains(email) {
+            errors.push(already_subscribed(row.line, email));
+            false
+        } else {
+            true
+        }
+    });
+
+    // 试运行时是校验通过的行数，实际导入时是真正写入的行数
+    let mut n_imported = rows.len();
+    if !dry_run && !rows.is_empty() {
+        let stored = store_subscribers(&pool, &rows, mode, list_id)
+            .await
+            .map_err(e500)?;
+        for row in rows {
+            let email = row.subscriber.email.as_ref();
+            // 在检查之后、写入之前被别的请求订阅了
+            if !stored.contains(email) {
+                errors.push(already_subscribed(row.line, email));
+            }
+        }
+        n_imported = stored.len();
+        tracing::info!(n_imported, n_errors = errors.len(), "Imported subscribers");
+    }
+    errors.sort_by_key(|e| e.line);
+
+    let summary = match (dry_run, mode) {
+        (true, ImportMode::Confirmed) => format!(
+            "Dry run: {} subscribers would be imported as confirmed. Nothing has been saved.",
+            n_imported
+        ),
+        (true, ImportMode::SendConfirmation) => format!(
+            "Dry run: {} subscribers would be imported and sent a confirmation email. \
+            Nothing has been saved.",
+            n_imported
+        ),
+        (false, ImportMode::Confirmed) => {
+            format!(
+                "{} subscribers have been imported as confirmed.",
+                n_imported
+            )
+        }
+        (false, ImportMode::SendConfirmation) => format!(
+            "{} subscribers have been imported. Their confirmation emails are being sent \
+            in the background.",
+            n_imported
+        ),
+    };
+    let mut rows_html = String::new();
+    for e in &errors {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{line}</td>
+            <td>{email}</td>
+            <td>{error}</td>
+        </tr>"#,
+            line = e.line,
+            email = encode_minimal(&e.email),
+            error = encode_minimal(&e.error),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    <p>{summary}</p>
+    <p>{n_errors} rows have errors:</p>
+    <table>
+        <tr>
+            <th>Line</th>
+            <th>Email</th>
+            <th>Error</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/subscribers/import">&lt;- Back</a></p>
+</body>
+</html>"#,
+            n_errors = errors.len(),
+        )))
+}
+
+fn already_subscribed(line: u64, email: &str) -> RowError {
+    RowError {
+        line,
+        email: email.to_string(),
+        error: "The email is already subscribed.".into(),
+    }
+}
+
+// 逐行校验，出错的行记录下来继续处理后面的行；只有表头不对时整个文件才被拒绝
+fn read_rows<R: Read>(reader: R) -> Result<(Vec<ImportRow>, Vec<RowError>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("The CSV file could not be read: {}", e))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (email_column, name_column) = match (column("email"), column("name")) {
+        (Some(email_column), Some(name_column)) => (email_column, name_column),
+        _ => {
+            return Err(
+                "The CSV file must have a header row with an email and a name column.".into(),
+            )
+        }
+    };
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen_emails = HashSet::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(RowError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    email: String::new(),
+                    error: format!("The row could not be read: {}", e),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let email = record.get(email_column).unwrap_or_default().to_string();
+        let name = record.get(name_column).unwrap_or_default().to_string();
+        let subscriber = match (
+            SubscriberEmail::parse(email.clone()),
+            SubscriberName::parse(name),
+        ) {
+            (Ok(email), Ok(name)) => NewSubscriber { email, name },
+            (Err(error), _) | (_, Err(error)) => {
+                errors.push(RowError { line, email, error });
+                continue;
+            }
+        };
+        if !seen_emails.insert(email.to_lowercase()) {
+            errors.push(RowError {
+                line,
+                email,
+                error: "The email already appears on an earlier row.".into(),
+            });
+            continue;
+        }
+        rows.push(ImportRow { line, subscriber });
+    }
+    Ok((rows, errors))
+}
+
+#[tracing::instrument(name = "Get existing subscriber emails", skip_all)]
+async fn get_existing_emails(
+    pool: &PgPool,
+    rows: &[ImportRow],
+) -> Result<HashSet<String>, anyhow::Error> {
+    let emails: Vec<String> = rows
+        .iter()
+        .map(|row| row.subscriber.email.as_ref().to_string())
+        .collect();
+    let existing = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE email = ANY($1)"#,
+        &emails
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up existing subscribers.")?;
+    Ok(existing.into_iter().map(|r| r.email).collect())
+}
+
+/// 在一个事务中批量写入订阅者、列表成员关系和确认令牌
+///
+/// 需要发送确认邮件时同时加入确认邮件队列，由后台 worker 发送。返回实际写入的邮箱地址。
+#[tracing::instrument(name = "Store imported subscribers", skip(pool, rows, mode))]
+async fn store_subscribers(
+    pool: &PgPool,
+    rows: &[ImportRow],
+    mode: ImportMode,
+    list_id: Uuid,
+) -> Result<HashSet<String>, anyhow::Error> {
+    let ids: Vec<Uuid> = rows.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = rows
+        .iter()
+        .map(|row| row.subscriber.email.as_ref().to_string())
+        .collect();
+    let names: Vec<String> = rows
+        .iter()
+        .map(|row| row.subscriber.name.as_ref().to_string())
+        .collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT id, email, name, now(), $4
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS t(id, email, name)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id, email
+        "#,
+        &ids,
+        &emails,
+        &names,
+        mode.status(),
+    )
+    .fetch_all(transaction.deref_mut())
+    .await
+    .context("Failed to insert the imported subscribers.")?;
+    let inserted_ids: Vec<Uuid> = inserted.iter().map(|r| r.id).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT $1, subscriber_id, $3 FROM UNNEST($2::uuid[]) AS subscriber_id
+        "#,
+        list_id,
+        &inserted_ids,
+        mode.status(),
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to add the imported subscribers to a list.")?;
+
+    if mode == ImportMode::SendConfirmation {
+        let tokens: Vec<String> = inserted
+            .iter()
+            .map(|_| generate_subscription_token())
+            .collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+            SELECT subscription_token, subscriber_id, now()
+            FROM UNNEST($1::text[], $2::uuid[]) AS t(subscription_token, subscriber_id)
+            "#,
+            &tokens,
+            &inserted_ids,
+        )
+        .execute(transaction.deref_mut())
+        .await
+        .context("Failed to store the confirmation tokens of the imported subscribers.")?;
+        enqueue_confirmation_emails(&mut transaction, &inserted_ids)
+            .await
+            .context("Failed to enqueue the confirmation emails of the imported subscribers.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store the imported subscribers.")?;
+    Ok(inserted.into_iter().map(|r| r.email).collect())
+}
@@ -0,0 +1,208 @@
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::route::{
+    generate_subscription_token, reset_pending_subscriber, send_confirmation_email, store_token,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_lists::confirm_list_memberships;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Manually confirm a subscriber", skip(pool))]
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed'
+        WHERE id = $1 AND status = 'pending_confirmation'
+        "#,
+        *subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to confirm a subscriber.")
+    .map_err(e500)?
+    .rows_affected();
+    if n_updated_rows > 0 {
+        confirm_list_memberships(&mut transaction, *subscriber_id)
+            .await
+            .context("Failed to confirm the list memberships of a subscriber.")
+            .map_err(e500)?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")
+        .map_err(e500)?;
+
+    if n_updated_rows == 0 {
+        FlashMessage::error("The subscriber does not exist or is not pending confirmation.").send();
+    } else {
+        FlashMessage::info("The subscriber has been confirmed.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Manually unsubscribe a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id = $1 AND status <> 'unsubscribed'
+        "#,
+        *subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to unsubscribe a subscriber.")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows == 0 {
+        FlashMessage::error("The subscriber does not exist or has already unsubscribed.").send();
+    } else {
+        FlashMessage::info("The subscriber has been unsubscribed.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+// 连同确认令牌、待发送的确认邮件和列表成员关系一起删除，投递记录只保存邮箱地址，不受影响
+#[tracing::instrument(name = "Delete a subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        *subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete the subscription tokens of a subscriber.")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM confirmation_email_queue WHERE subscriber_id = $1"#,
+        *subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete the queued confirmation email of a subscriber.")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM list_memberships WHERE subscriber_id = $1"#,
+        *subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await
+    .context("Failed to delete the list memberships of a subscriber.")
+    .map_err(e500)?;
+    let n_deleted_rows = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, *subscriber_id)
+        .execute(transaction.deref_mut())
+        .await
+        .context("Failed to delete a subscriber.")
+        .map_err(e500)?
+        .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")
+        .map_err(e500)?;
+
+    if n_deleted_rows == 0 {
+        FlashMessage::error("The subscriber does not exist.").send();
+    } else {
+        FlashMessage::info("The subscriber has been deleted.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+// 和重新订阅一样：作废旧令牌，签发新令牌，邮件发送失败时回滚
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(pool, email_client, base_url)
+)]
+pub async fn resend_confirmation_email(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let subscriber = sqlx::query!(
+        r#"SELECT email, name, status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        *subscriber_id
+    )
+    .fetch_optional(transaction.deref_mut())
+    .await
+    .context("Failed to retrieve a subscriber.")
+    .map_err(e500)?;
+    let subscriber = match subscriber {
+        Some(s) if s.status == "pending_confirmation" => s,
+        _ => {
+            FlashMessage::error("The subscriber does not exist or is not pending confirmation.")
+                .send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+    let new_subscriber = NewSubscriber {
+        email: SubscriberEmail::parse(subscriber.email)
+            .map_err(anyhow::Error::msg)
+            .map_err(e500)?,
+        name: SubscriberName::parse(subscriber.name)
+            .map_err(anyhow::Error::msg)
+            .map_err(e500)?,
+    };
+
+    reset_pending_subscriber(&mut transaction, *subscriber_id)
+        .await
+        .context("Failed to reset a pending subscriber.")
+        .map_err(e500)?;
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, *subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a subscriber.")
+        .map_err(e500)?;
+    send_confirmation_email(
+        &email_client,
+        new_subscriber,
+        &base_url.0,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscription token.")
+        .map_err(e500)?;
+
+    FlashMessage::info("The confirmation email has been sent again.").send();
+    Ok(see_other("/admin/subscribers"))
+}
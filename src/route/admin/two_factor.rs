@@ -0,0 +1,168 @@
+use crate::authentication::{
+    disable_two_factor, enable_two_factor, generate_totp_secret, get_totp_secret, otpauth_qr_code,
+    totp, verify_second_factor, UserId,
+};
+use crate::route::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn two_factor_settings(
+    session: TypedSession,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let enabled = get_totp_secret(**user_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_some();
+    let body_html = if enabled {
+        r#"<p>Two-factor authentication is enabled.</p>
+    <form action="/admin/two-factor/disable" method="post">
+        <label>Verification code
+            <input type="text" placeholder="Enter a code to confirm" name="code">
+        </label>
+        <button type="submit">Disable two-factor authentication</button>
+    </form>"#
+            .to_string()
+    } else {
+        // 刷新页面时沿用会话中尚未确认的密钥，避免用户已扫描的二维码失效
+        let secret = match session.get_pending_totp_secret().map_err(e500)? {
+            Some(secret) => secret,
+            None => {
+                let secret = generate_totp_secret();
+                session.insert_pending_totp_secret(&secret).map_err(e500)?;
+                secret
+            }
+        };
+        let username = get_username(**user_id, &pool).await.map_err(e500)?;
+        let totp = totp(&secret, &username).map_err(e500)?;
+        let qr_code = otpauth_qr_code(&totp).map_err(e500)?;
+        format!(
+            r#"<p>Scan the QR code with your authenticator app, then enter the code it shows.</p>
+    {qr_code}
+    <p>Or add this URI manually: <code>{uri}</code></p>
+    <p>Secret: <code>{secret}</code></p>
+    <form action="/admin/two-factor" method="post">
+        <label>Verification code
+            <input type="text" placeholder="Enter the 6-digit code" name="code">
+        </label>
+        <button type="submit">Enable two-factor authentication</button>
+    </form>"#,
+            uri = encode_minimal(&totp.get_url()),
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {msg_html}
+    {body_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Confirm two-factor enrolment",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn confirm_two_factor(
+    form: web::Form<FormData>,
+    session: TypedSession,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let secret = match session.get_pending_totp_secret().map_err(e500)? {
+        Some(secret) => secret,
+        None => return Ok(see_other("/admin/two-factor")),
+    };
+    let is_valid = totp(&secret, "")
+        .map_err(e500)?
+        .check_current(form.code.trim())
+        .map_err(e500)?;
+    if !is_valid {
+        FlashMessage::error("The verification code is incorrect.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    let recovery_codes = enable_two_factor(**user_id, &secret, &pool)
+        .await
+        .map_err(e500)?;
+    session.remove_pending_totp_secret();
+
+    // 恢复码只展示这一次，所以直接渲染页面而不是重定向
+    let mut codes_html = String::new();
+    for code in &recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    <p>Two-factor authentication has been enabled.</p>
+    <p>Store these recovery codes somewhere safe. Each of them can be used once
+    instead of a verification code, and they will not be shown again.</p>
+    <ul>
+        {codes_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    name = "Disable two-factor authentication",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn remove_two_factor(
+    form: web::Form<FormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !verify_second_factor(**user_id, &form.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The verification code is incorrect.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    disable_two_factor(**user_id, &pool).await.map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been disabled.").send();
+    Ok(see_other("/admin/two-factor"))
+}
@@ -0,0 +1,148 @@
+use crate::authentication::{Role, UserId};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct AdminUser {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: String,
+    is_active: bool,
+}
+
+pub async fn list_users(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let users = get_users(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for user in &users {
+        // 不允许修改自己的角色或停用自己，这样至少总会留下一个 owner
+        let actions_html = if user.user_id == **user_id || !user.is_active {
+            "<td></td><td></td>".to_string()
+        } else {
+            format!(
+                r#"<td>
+                <form action="/admin/users/{id}/role" method="post">
+                    <select name="role">{options}</select>
+                    <button type="submit">Change role</button>
+                </form>
+            </td>
+            <td>
+                <form action="/admin/users/{id}/deactivate" method="post">
+                    <button type="submit">Deactivate</button>
+                </form>
+            </td>"#,
+                id = user.user_id,
+                options = role_options(&user.role),
+            )
+        };
+        let status = if user.is_active {
+            "active"
+        } else {
+            "deactivated"
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{username}</td>
+            <td>{email}</td>
+            <td>{role}</td>
+            <td>{status}</td>
+            {actions_html}
+        </tr>"#,
+            username = encode_minimal(&user.username),
+            email = encode_minimal(user.email.as_deref().unwrap_or("")),
+            role = user.role,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Users</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Username</th>
+            <th>Email</th>
+            <th>Role</th>
+            <th>Status</th>
+            <th></th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    <h2>Invite a user</h2>
+    <form action="/admin/users" method="post">
+        <label>Username
+            <input type="text" placeholder="Enter the username" name="username">
+        </label>
+        <label>Email
+            <input type="text" placeholder="Enter the email address" name="email">
+        </label>
+        <label>Role
+            <select name="role">{options}</select>
+        </label>
+        <button type="submit">Invite</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            options = role_options(Role::Viewer.as_str()),
+        )))
+}
+
+fn role_options(selected: &str) -> String {
+    let mut options_html = String::new();
+    for role in Role::ALL {
+        write!(
+            options_html,
+            r#"<option value="{role}"{selected}>{role}</option>"#,
+            selected = if role.as_str() == selected {
+                " selected"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+    options_html
+}
+
+#[tracing::instrument(name = "Get admin users", skip(pool))]
+async fn get_users(pool: &PgPool) -> Result<Vec<AdminUser>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        AdminUser,
+        r#"
+        SELECT user_id, username, email, role, is_active
+        FROM users
+        ORDER BY is_active DESC, username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve admin users.")?;
+    Ok(users)
+}
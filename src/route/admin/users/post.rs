@@ -0,0 +1,231 @@
+use crate::authentication::{create_user, Role, UserId};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::route::{issue_reset_token, reset_link};
+use crate::startup::{ApplicationBaseUrl, InvitationTokenTtl};
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    email: String,
+    role: String,
+}
+
+#[tracing::instrument(
+    name = "Invite a user",
+    skip_all,
+    fields(user_id=%*user_id, username=%form.username)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    ttl: web::Data<InvitationTokenTtl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let InviteFormData {
+        username,
+        email,
+        role,
+    } = form.0;
+    let username = username.trim().to_string();
+    if username.is_empty() {
+        FlashMessage::error("The username cannot be empty.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let (email, role) = match (SubscriberEmail::parse(email), Role::parse(&role)) {
+        (Ok(email), Ok(role)) => (email, role),
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    // 新用户没有可用的密码，通过邀请邮件中的一次性链接（与重置密码相同的令牌，但有效期更长）设置密码
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let new_user_id = match create_user(&mut transaction, &username, email.as_ref(), role).await {
+        Ok(new_user_id) => new_user_id,
+        Err(e) if is_unique_violation(&e) => {
+            FlashMessage::error("A user with this username or email already exists.").send();
+            return Ok(see_other("/admin/users"));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    let token = issue_reset_token(&mut transaction, new_user_id, ttl.0)
+        .await
+        .map_err(e500)?;
+    // 邮件发送失败时回滚，避免留下一个没人能设置密码的账号
+    send_invitation_email(
+        &email_client,
+        &email,
+        &base_url.0,
+        &username,
+        role,
+        &token,
+        ttl.0,
+    )
+    .await
+    .context("Failed to send an invitation email.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new user.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "{} has been invited as {}.",
+        encode_minimal(&username),
+        role
+    ))
+    .send();
+    Ok(see_other("/admin/users"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[tracing::instrument(
+    name = "Change the role of a user",
+    skip_all,
+    fields(user_id=%*user_id, target_user_id=%*target_user_id)
+)]
+pub async fn change_user_role(
+    target_user_id: web::Path<Uuid>,
+    form: web::Form<RoleFormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(encode_minimal(&e)).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    // 不允许修改自己，这样操作者本身始终是一个 owner，系统中不会失去所有 owner
+    if *target_user_id == **user_id {
+        FlashMessage::error("You cannot change your own role.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE users
+        SET role = $2
+        WHERE user_id = $1 AND is_active
+        "#,
+        *target_user_id,
+        role.as_str()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to change the role of a user")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows == 0 {
+        not_active_message().send();
+    } else {
+        FlashMessage::info(format!("The role has been changed to {}.", role)).send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "Deactivate a user",
+    skip_all,
+    fields(user_id=%*user_id, target_user_id=%*target_user_id)
+)]
+pub async fn deactivate_user(
+    target_user_id: web::Path<Uuid>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if *target_user_id == **user_id {
+        FlashMessage::error("You cannot deactivate yourself.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    // `reject_anonymous_users` 每次请求都会检查 `is_active`，
+    // 所以该用户现有的会话会在下一次请求时失效
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE users
+        SET is_active = false
+        WHERE user_id = $1 AND is_active
+        "#,
+        *target_user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to deactivate a user")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows == 0 {
+        not_active_message().send();
+    } else {
+        FlashMessage::info("The user has been deactivated.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+fn not_active_message() -> FlashMessage {
+    FlashMessage::error("The user does not exist or has already been deactivated.")
+}
+
+fn is_unique_violation(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+#[tracing::instrument(
+    name = "Send an invitation email to a new user",
+    skip(email_client, recipient, base_url, token)
+)]
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    base_url: &str,
+    username: &str,
+    role: Role,
+    token: &str,
+    ttl: Duration,
+) -> Result<(), anyhow::Error> {
+    let link = reset_link(base_url, token);
+    let hours = ttl.as_secs() / 3600;
+    let html_body = format!(
+        "You have been invited to the newsletter admin area as {role}.<br />\
+        Username: {username}<br />\
+        Click <a href=\"{link}\">here</a> to choose your password.<br />\
+        The link can only be used once and expires in {hours} hours.",
+        username = encode_minimal(username),
+    );
+    let plain_body = format!(
+        "You have been invited to the newsletter admin area as {role}.\n\
+        Username: {username}\n\
+        Visit {link} to choose your password.\n\
+        The link can only be used once and expires in {hours} hours.",
+    );
+    email_client
+        .send_email(recipient, "You have been invited", &html_body, &plain_body)
+        .await
+}
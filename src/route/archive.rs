@@ -0,0 +1,135 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct ArchivedIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+struct ArchivedIssueContent {
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+/// 公开的期刊归档，只列出已经开始投递的期刊
+///
+/// 计划发布的期刊在进入投递队列时才会填写 `published_at`，在此之前不会出现在这里。
+pub async fn archive(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_archived_issues(&pool).await.map_err(e500)?;
+    let mut items_html = String::new();
+    for issue in &issues {
+        writeln!(
+            items_html,
+            r#"<li><a href="/archive/{id}">{title}</a> - {published_on}</li>"#,
+            id = issue.newsletter_issue_id,
+            title = encode_minimal(&issue.title),
+            published_on = issue.published_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        items_html.push_str("<li>No issues have been published yet.</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter archive</title>
+</head>
+<body>
+    <h1>Newsletter archive</h1>
+    <ul>
+        {items_html}
+    </ul>
+</body>
+</html>"#,
+        )))
+}
+
+pub async fn archived_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = match get_archived_issue(&pool, *issue_id).await.map_err(e500)? {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    // `html_content` 由有发布权限的管理员编写，按原样渲染
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <p>Published on {published_on}</p>
+    {html_content}
+    <p><a href="/archive">&lt;- All issues</a></p>
+</body>
+</html>"#,
+            title = encode_minimal(&issue.title),
+            published_on = issue.published_at.format("%Y-%m-%d"),
+            html_content = issue.html_content,
+        )))
+}
+
+#[tracing::instrument(name = "Get archived newsletter issues", skip(pool))]
+async fn get_archived_issues(pool: &PgPool) -> Result<Vec<ArchivedIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE published_at IS NOT NULL
+        ORDER BY published_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the archived newsletter issues.")?;
+    Ok(issues)
+}
+
+#[tracing::instrument(name = "Get an archived newsletter issue", skip(pool))]
+async fn get_archived_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<ArchivedIssueContent>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        ArchivedIssueContent,
+        r#"
+        SELECT
+            title,
+            html_content,
+            published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND published_at IS NOT NULL
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an archived newsletter issue.")?;
+    Ok(issue)
+}
@@ -0,0 +1,210 @@
+use crate::configuration::FeedSettings;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::http::header::{
+    ETag, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified, IF_NONE_MATCH,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+const FEED_TITLE: &str = "Newsletter";
+
+struct FeedItem {
+    newsletter_issue_id: Uuid,
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    feed_settings: web::Data<FeedSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let items = get_feed_items(&pool, feed_settings.item_limit)
+        .await
+        .map_err(e500)?;
+    let body = atom(&base_url.0, &items);
+    Ok(conditional_response(
+        &request,
+        "application/atom+xml; charset=utf-8",
+        body,
+        last_modified(&items),
+    ))
+}
+
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    feed_settings: web::Data<FeedSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let items = get_feed_items(&pool, feed_settings.item_limit)
+        .await
+        .map_err(e500)?;
+    let body = rss(&base_url.0, &items);
+    Ok(conditional_response(
+        &request,
+        "application/rss+xml; charset=utf-8",
+        body,
+        last_modified(&items),
+    ))
+}
+
+// 期刊发布后不会再修改，所以最新一期的发布时间就是整个 feed 的修改时间
+fn last_modified(items: &[FeedItem]) -> Option<DateTime<Utc>> {
+    items.iter().map(|item| item.published_at).max()
+}
+
+/// 带上 `ETag` 和 `Last-Modified`，并在条件请求命中时返回 304
+///
+/// 按 RFC 9110，请求同时带有 `If-None-Match` 时忽略 `If-Modified-Since`。
+fn conditional_response(
+    request: &HttpRequest,
+    content_type: &str,
+    body: String,
+    last_modified: Option<DateTime<Utc>>,
+) -> HttpResponse {
+    let etag = EntityTag::new_strong(hex::encode(&Sha256::digest(body.as_bytes())[..16]));
+    let not_modified = if request.headers().contains_key(IF_NONE_MATCH) {
+        match IfNoneMatch::parse(request) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            Err(_) => false,
+        }
+    } else {
+        match (IfModifiedSince::parse(request), last_modified) {
+            (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+                // HTTP 日期只精确到秒
+                let since = DateTime::<Utc>::from(SystemTime::from(since));
+                last_modified.timestamp() <= since.timestamp()
+            }
+            _ => false,
+        }
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(SystemTime::from(
+            last_modified,
+        ))));
+    }
+    if not_modified {
+        response.finish()
+    } else {
+        response.content_type(content_type).body(body)
+    }
+}
+
+fn atom(base_url: &str, items: &[FeedItem]) -> String {
+    // Atom 要求 `updated`，没有任何期刊时使用一个固定的时间，保证 ETag 不变
+    let updated = last_modified(items).unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+    let mut entries = String::new();
+    for item in items {
+        writeln!(
+            entries,
+            r#"  <entry>
+    <title>{title}</title>
+    <id>urn:uuid:{id}</id>
+    <link rel="alternate" type="text/html" href="{base_url}/archive/{id}"/>
+    <published>{published}</published>
+    <updated>{published}</updated>
+    <content type="html">{content}</content>
+  </entry>"#,
+            title = encode_minimal(&item.title),
+            id = item.newsletter_issue_id,
+            base_url = encode_minimal(base_url),
+            published = item.published_at.to_rfc3339(),
+            content = encode_minimal(&item.html_content),
+        )
+        .unwrap();
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>{FEED_TITLE}</title>
+  <id>{base_url}/archive</id>
+  <link rel="self" type="application/atom+xml" href="{base_url}/feed.atom"/>
+  <link rel="alternate" type="text/html" href="{base_url}/archive"/>
+  <updated>{updated}</updated>
+  <author><name>{FEED_TITLE}</name></author>
+{entries}</feed>
+"#,
+        base_url = encode_minimal(base_url),
+        updated = updated.to_rfc3339(),
+    )
+}
+
+fn rss(base_url: &str, items: &[FeedItem]) -> String {
+    let mut channel_items = String::new();
+    for item in items {
+        writeln!(
+            channel_items,
+            r#"    <item>
+      <title>{title}</title>
+      <link>{base_url}/archive/{id}</link>
+      <guid>{base_url}/archive/{id}</guid>
+      <pubDate>{published}</pubDate>
+      <description>{content}</description>
+    </item>"#,
+            title = encode_minimal(&item.title),
+            id = item.newsletter_issue_id,
+            base_url = encode_minimal(base_url),
+            published = item.published_at.to_rfc2822(),
+            content = encode_minimal(&item.html_content),
+        )
+        .unwrap();
+    }
+    let last_build_date = last_modified(items)
+        .map(|date| format!("\n    <lastBuildDate>{}</lastBuildDate>", date.to_rfc2822()))
+        .unwrap_or_default();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+  <channel>
+    <title>{FEED_TITLE}</title>
+    <link>{base_url}/archive</link>
+    <description>Past issues of our newsletter</description>{last_build_date}
+{channel_items}  </channel>
+</rss>
+"#,
+        base_url = encode_minimal(base_url),
+    )
+}
+
+// 和 `/archive` 一样，只包含已经开始投递的期刊
+#[tracing::instrument(name = "Get feed items", skip(pool))]
+async fn get_feed_items(pool: &PgPool, limit: i64) -> Result<Vec<FeedItem>, anyhow::Error> {
+    let items = sqlx::query_as!(
+        FeedItem,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            html_content,
+            published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE published_at IS NOT NULL
+        ORDER BY published_at DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the feed items.")?;
+    Ok(items)
+}
@@ -1,5 +1,12 @@
 mod get;
+mod password_reset;
 mod post;
+mod two_factor;
 
 pub use get::login_from;
-pub use post::login;
\ No newline at end of file
+pub use password_reset::{
+    forgot_password_form, issue_reset_token, request_password_reset, reset_link, reset_password,
+    reset_password_form,
+};
+pub use post::login;
+pub use two_factor::{login_second_factor, login_second_factor_form};
\ No newline at end of file
@@ -38,6 +38,7 @@ pub async fn login_from(flash_messages: IncomingFlashMessages) -> HttpResponse {
             </label>
             <button type="submit">Login</button>
             </form>
+            <p><a href="/login/forgot">Forgot your password?</a></p>
             </body>
             </html>"#,
         ));
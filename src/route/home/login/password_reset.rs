@@ -0,0 +1,407 @@
+use crate::authentication::{change_password, SetPasswordError};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::{ApplicationBaseUrl, PasswordResetTokenTtl};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_attribute;
+use rand::distr::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub async fn forgot_password_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Forgot password</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/login/forgot" method="post">
+        <label>Username or email
+            <input
+                type="text"
+                placeholder="Enter your username or email"
+                name="username"
+            >
+        </label>
+        <button type="submit">Send reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back</a></p>
+</body>
+</html>"#,
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ForgotPasswordFormData {
+    username: String,
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url, ttl),
+    fields(username=%form.username)
+)]
+pub async fn request_password_reset(
+    form: web::Form<ForgotPasswordFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    ttl: web::Data<PasswordResetTokenTtl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // 无论账号是否存在都返回同样的提示，避免被用来探测用户名
+    if let Some((user_id, email)) = get_user_email(&pool, form.username.trim())
+        .await
+        .map_err(e500)?
+    {
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")
+            .map_err(e500)?;
+        let token = issue_reset_token(&mut transaction, user_id, ttl.0)
+            .await
+            .map_err(e500)?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a password reset token.")
+            .map_err(e500)?;
+        match SubscriberEmail::parse(email) {
+            Ok(email) => {
+                if let Err(e) =
+                    send_password_reset_email(&email_client, &email, &base_url.0, &token).await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a password reset email."
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("The stored email address of the user is invalid: {}", e),
+        }
+    }
+
+    FlashMessage::info(
+        "If the account exists, a link to reset its password has been sent to its email address.",
+    )
+    .send();
+    Ok(see_other("/login/forgot"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetParameters {
+    token: String,
+}
+
+pub async fn reset_password_form(
+    parameters: web::Query<ResetParameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if get_valid_reset_token(&mut transaction, &parameters.token)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Ok(invalid_token_redirect());
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reset password</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/login/reset" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>New password
+            <input
+                type="password"
+                placeholder="Enter new password"
+                name="new_password"
+            >
+        </label>
+        <br>
+        <label>Confirm new password
+            <input
+                type="password"
+                placeholder="Type the new password again"
+                name="new_password_check"
+            >
+        </label>
+        <br>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+            token = encode_attribute(&parameters.token),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetPasswordFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Reset a password",
+    skip(form, pool),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn reset_password(
+    form: web::Form<ResetPasswordFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ResetPasswordFormData {
+        token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other(&format!(
+            "/login/reset?token={}",
+            urlencoding::encode(&token)
+        )));
+    }
+
+    // 令牌行在事务结束前保持锁定，同一个令牌的并发请求只有一个能成功
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let user_id = match get_valid_reset_token(&mut transaction, &token)
+        .await
+        .map_err(e500)?
+    {
+        Some(user_id) => user_id,
+        None => return Ok(invalid_token_redirect()),
+    };
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    if let Err(e) = change_password(&mut transaction, user_id, new_password).await {
+        return match e {
+            SetPasswordError::PolicyViolations(violations) => {
+                for violation in violations {
+                    FlashMessage::error(violation.to_string()).send();
+                }
+                Ok(see_other(&format!(
+                    "/login/reset?token={}",
+                    urlencoding::encode(&token)
+                )))
+            }
+            SetPasswordError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+    revoke_reset_tokens_and_sessions(&mut transaction, user_id)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")
+        .map_err(e500)?;
+
+    FlashMessage::info("Your password has been reset. Please log in with your new password.")
+        .send();
+    Ok(see_other("/login"))
+}
+
+fn invalid_token_redirect() -> HttpResponse {
+    FlashMessage::error("This password reset link is invalid or has expired.").send();
+    see_other("/login/forgot")
+}
+
+/// 生成一个随机的 32 个字符长 区分大小写的重置令牌
+fn generate_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[tracing::instrument(name = "Get the email of a user", skip(pool))]
+async fn get_user_email(
+    pool: &PgPool,
+    username: &str,
+) -> Result<Option<(Uuid, String)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email as "email!"
+        FROM users
+        WHERE
+            (username = $1 OR email = $1) AND
+            email IS NOT NULL AND
+            is_active
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the email of a user.")?;
+    Ok(row.map(|r| (r.user_id, r.email)))
+}
+
+/// 在调用方的事务中签发一个在 `ttl` 后过期的重置令牌，只保存它的摘要，返回明文令牌
+///
+/// 邀请新用户时也用它生成设置密码的链接，只是有效期更长。
+#[tracing::instrument(name = "Issue a password reset token", skip(transaction))]
+pub async fn issue_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    ttl: Duration,
+) -> Result<String, anyhow::Error> {
+    let token = generate_reset_token();
+    let expires_at: DateTime<Utc> = Utc::now() + chrono::Duration::from_std(ttl)?;
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, now(), $3)
+        "#,
+        hash_reset_token(&token),
+        user_id,
+        expires_at
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store a password reset token.")?;
+    Ok(token)
+}
+
+pub fn reset_link(base_url: &str, token: &str) -> String {
+    format!("{}/login/reset?token={}", base_url, token)
+}
+
+// 返回令牌所属的用户，令牌不存在、已使用或已过期时返回 `None`
+#[tracing::instrument(name = "Get a valid password reset token", skip(transaction, token))]
+async fn get_valid_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT t.user_id, t.expires_at, t.used_at
+        FROM password_reset_tokens t
+        JOIN users u USING (user_id)
+        WHERE t.token_hash = $1 AND u.is_active
+        FOR UPDATE OF t
+        "#,
+        hash_reset_token(token)
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to retrieve a password reset token.")?;
+
+    Ok(row
+        .filter(|r| r.used_at.is_none() && r.expires_at > Utc::now())
+        .map(|r| r.user_id))
+}
+
+// 密码重置后，该用户所有未使用的重置链接和已登录的会话都随之失效
+#[tracing::instrument(name = "Revoke reset tokens and sessions", skip(transaction))]
+async fn revoke_reset_tokens_and_sessions(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = $2
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id,
+        now
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to mark the password reset tokens as used.")?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET sessions_revoked_at = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        now
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to revoke the sessions of a user.")?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Send a password reset email",
+    skip(email_client, recipient, base_url, token)
+)]
+async fn send_password_reset_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let reset_link = reset_link(base_url, token);
+    let html_body = format!(
+        "Someone asked to reset the password of your account.<br />\
+        Click <a href=\"{}\">here</a> to choose a new password.<br />\
+        If it was not you, you can safely ignore this email.",
+        reset_link
+    );
+    let plain_body = format!(
+        "Someone asked to reset the password of your account.\n\
+        Visit {} to choose a new password.\n\
+        If it was not you, you can safely ignore this email.",
+        reset_link
+    );
+    email_client
+        .send_email(recipient, "Reset your password", &html_body, &plain_body)
+        .await
+}
@@ -1,11 +1,14 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::authentication::{
+    get_totp_secret, validate_credentials_with_throttling, AuthError, Credentials,
+};
+use crate::configuration::LoginThrottlingSettings;
 use crate::route::error_chain_fmt;
 use crate::session_state::TypedSession;
 use actix_web::body::BoxBody;
 use actix_web::error::InternalError;
 use actix_web::http::header::LOCATION;
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use actix_web_flash_messages::FlashMessage;
 use hmac::{Hmac, Mac};
 use secrecy::Secret;
@@ -19,13 +22,15 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(form, pool, session, request, throttling_settings),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    request: HttpRequest,
+    throttling_settings: web::Data<LoginThrottlingSettings>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
@@ -33,21 +38,37 @@ pub async fn login(
     };
     tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
 
-    match validate_credentials(credentials, &pool).await {
+    let client_ip = request.peer_addr().map(|addr| addr.ip());
+    match validate_credentials_with_throttling(credentials, client_ip, &throttling_settings, &pool)
+        .await
+    {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
-            session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            // 启用了两步验证的用户此时只算“密码已验证”，还不能访问后台
+            let totp_secret = get_totp_secret(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            let location = if totp_secret.is_some() {
+                session.renew();
+                session
+                    .insert_pending_second_factor(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                "/login/two-factor"
+            } else {
+                session
+                    .log_in(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                "/admin/dashboard"
+            };
             Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
+                .insert_header((LOCATION, location))
                 .finish())
         }
 
         Err(e) => {
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::TooManyAttempts(_) => LoginError::TooManyAttempts(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             FlashMessage::error(e.to_string()).send();
@@ -72,6 +93,9 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    // 与用户是否存在无关，不会暴露账号信息
+    #[error("Too many failed login attempts - please try again later.")]
+    TooManyAttempts(#[source] anyhow::Error),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -0,0 +1,133 @@
+use crate::authentication::{verify_second_factor_with_throttling, AuthError};
+use crate::configuration::LoginThrottlingSettings;
+use crate::session_state::{PendingSecondFactor, TypedSession};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+// 密码验证通过后，必须在这段时间内完成两步验证，否则需要重新输入密码
+const SECOND_FACTOR_TIMEOUT_MINUTES: i64 = 5;
+// 同一次登录中输错这么多次验证码后，需要重新输入密码
+const MAX_SECOND_FACTOR_ATTEMPTS: u32 = 3;
+
+pub async fn login_second_factor_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if get_pending_login(&session)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/login/two-factor" method="post">
+        <label>Verification code
+            <input
+                type="text"
+                placeholder="Enter the code from your app or a recovery code"
+                name="code"
+                autocomplete="one-time-code"
+            >
+        </label>
+        <button type="submit">Verify</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Verify the second factor of a login",
+    skip(form, session, pool, request, throttling_settings),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn login_second_factor(
+    form: web::Form<FormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+    throttling_settings: web::Data<LoginThrottlingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let pending = match get_pending_login(&session)? {
+        Some(pending) => pending,
+        None => {
+            FlashMessage::error("Your login has expired - please log in again.").send();
+            return Ok(see_other("/login"));
+        }
+    };
+    tracing::Span::current().record("user_id", &tracing::field::display(&pending.user_id));
+
+    let client_ip = request.peer_addr().map(|addr| addr.ip());
+    match verify_second_factor_with_throttling(
+        pending.user_id,
+        &form.code,
+        client_ip,
+        &throttling_settings,
+        &pool,
+    )
+    .await
+    {
+        Ok(()) => {
+            session.log_in(pending.user_id).map_err(e500)?;
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(AuthError::InvalidCredentials(_)) => {
+            let failed_attempts = session
+                .record_second_factor_failure(pending)
+                .map_err(e500)?;
+            if failed_attempts >= MAX_SECOND_FACTOR_ATTEMPTS {
+                session.remove_pending_second_factor();
+                FlashMessage::error("Too many incorrect codes - please log in again.").send();
+                return Ok(see_other("/login"));
+            }
+            FlashMessage::error("The verification code is incorrect.").send();
+            Ok(see_other("/login/two-factor"))
+        }
+        Err(AuthError::TooManyAttempts(_)) => {
+            session.remove_pending_second_factor();
+            FlashMessage::error("Too many failed login attempts - please try again later.").send();
+            Ok(see_other("/login"))
+        }
+        Err(e @ AuthError::UnexpectedError(_)) => Err(e500(e)),
+    }
+}
+
+// 超时的待验证状态会被清除，调用方把它当作没有登录处理
+fn get_pending_login(
+    session: &TypedSession,
+) -> Result<Option<PendingSecondFactor>, actix_web::Error> {
+    let pending = session.get_pending_second_factor().map_err(e500)?;
+    match pending {
+        Some(pending)
+            if Utc::now() - pending.password_verified_at
+                > Duration::minutes(SECOND_FACTOR_TIMEOUT_MINUTES) =>
+        {
+            session.remove_pending_second_factor();
+            Ok(None)
+        }
+        pending => Ok(pending),
+    }
+}
@@ -1,7 +1,18 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials};
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::authentication::{
+    validate_api_token, validate_credentials_with_throttling, ApiTokenScope, AuthError,
+    Credentials, Role,
+};
+use crate::configuration::{IdempotencySettings, LoginThrottlingSettings};
+use crate::domain::ListSlug;
+use crate::idempotency::IdempotencyKey::IdempotencyKey;
+use crate::idempotency::{request_in_progress, save_response, try_processing, NextAction};
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::route::admin::newsletter::{
+    get_delivery_counts, get_issue_title, insert_newsletter_issue, DeliveryCounts,
+};
 use crate::route::error_chain_fmt;
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_lists::{default_list_slug, resolve_list_ids, ListLookupError};
 use actix_web::http::header::{HeaderMap, HeaderValue};
 use actix_web::http::{header, StatusCode};
 use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
@@ -11,11 +22,15 @@ use base64::Engine;
 use secrecy::{Secret};
 use sqlx::PgPool;
 use std::fmt::Formatter;
+use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct BodyData {
     title: String,
     content: Content,
+    // 目标列表的标识，省略时发送到默认列表
+    #[serde(default)]
+    lists: Vec<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -24,14 +39,31 @@ pub struct Content {
     text: String,
 }
 
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
+#[derive(serde::Serialize)]
+struct PublishResponse {
+    issue_id: Uuid,
+    status_url: String,
+}
+
+#[derive(serde::Serialize)]
+struct IssueStatusResponse {
+    issue_id: Uuid,
+    title: String,
+    deliveries: DeliveryCounts,
 }
 
 #[derive(thiserror::Error)]
 pub enum PublishError {
     #[error("Authentication failed.")]
     AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    // 认证成功，但令牌的权限范围或用户的角色不允许这个操作
+    #[error("Forbidden.")]
+    Forbidden(#[source] anyhow::Error),
+    // 失败次数过多被临时锁定，附带距离解除锁定的时间
+    #[error("Too many failed attempts.")]
+    TooManyAttempts(std::time::Duration),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -56,58 +88,240 @@ impl ResponseError for PublishError {
                     .insert(header::WWW_AUTHENTICATE, header_value);
                 response
             }
+            PublishError::ValidationError(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            PublishError::Forbidden(_) => HttpResponse::new(StatusCode::FORBIDDEN),
+            PublishError::TooManyAttempts(retry_after) => HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.as_secs().to_string()))
+                .finish(),
         }
     }
 }
 
+/// 期刊写入投递队列后立即返回 `202 Accepted`，邮件由后台 worker 发送
+///
+/// 带有 `Idempotency-Key` 标头的请求可以安全重试：同一个键只会创建一期期刊，
+/// 重复的请求直接返回第一次保存的响应。
 #[tracing::instrument(
     name = "Publish a newsletter issue",
-    skip(body, pool, email_client, request),
+    skip(body, pool, base_url, idempotency_settings, request, throttling_settings),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     body: web::Json<BodyData>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    idempotency_settings: web::Data<IdempotencySettings>,
     request: HttpRequest,
+    throttling_settings: web::Data<LoginThrottlingSettings>,
 ) -> Result<HttpResponse, PublishError> {
-    let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let user_id = authenticate(
+        &request,
+        ApiTokenScope::PublishNewsletters,
+        &pool,
+        &throttling_settings,
+    )
+    .await?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+    let idempotency_key = idempotency_key(request.headers())?;
+    let list_ids = target_list_ids(&body.lists, &pool).await?;
 
-    let user_id = validate_credentials(credentials, &pool)
+    let mut transaction = match &idempotency_key {
+        Some(idempotency_key) => match try_processing(
+            &pool,
+            idempotency_key,
+            user_id,
+            idempotency_settings.retention(),
+            idempotency_settings.concurrent_request_timeout(),
+        )
+        .await?
+        {
+            NextAction::StartProcessing(t) => t,
+            NextAction::ReturnSaveResponse(saved_response) => return Ok(saved_response),
+            NextAction::RequestInProgress => {
+                return Ok(request_in_progress(
+                    idempotency_settings.concurrent_request_timeout(),
+                ));
+            }
+        },
+        None => pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?,
+    };
+
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &body.title,
+        &body.content.text,
+        &body.content.html,
+        None,
+        &list_ids,
+        user_id,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    enqueue_delivery_tasks(&mut transaction, issue_id)
         .await
-        .map_err(|e| match e {
-            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
-            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
-        })?;
+        .context("Failed to enqueue delivery tasks")?;
+
+    let status_url = format!("{}/newsletters/{}", base_url.0, issue_id);
+    let response = HttpResponse::Accepted()
+        .insert_header((header::LOCATION, status_url.clone()))
+        .json(PublishResponse {
+            issue_id,
+            status_url,
+        });
+    let response = match &idempotency_key {
+        Some(idempotency_key) => {
+            save_response(transaction, idempotency_key, user_id, response).await?
+        }
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to store a newsletter issue.")?;
+            response
+        }
+    };
+    Ok(response)
+}
+
+/// 返回期刊的投递进度，`publish_newsletter` 响应中的 `status_url` 指向这里
+#[tracing::instrument(
+    name = "Get the status of a newsletter issue",
+    skip(pool, request, throttling_settings),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+pub async fn newsletter_issue_status(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+    throttling_settings: web::Data<LoginThrottlingSettings>,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(
+        &request,
+        ApiTokenScope::ReadNewsletters,
+        &pool,
+        &throttling_settings,
+    )
+    .await?;
     tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
-    let subscribers = get_confirmed_subscribers(&pool).await?;
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(
-                        &subscriber.email,
-                        &body.title,
-                        &body.content.html,
-                        &body.content.text,
-                    )
-                    .await
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    })?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid",
-                );
-            }
+    let issue_id = issue_id.into_inner();
+    let title = match get_issue_title(&pool, issue_id).await? {
+        Some(title) => title,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let deliveries = get_delivery_counts(&pool, issue_id).await?;
+    Ok(HttpResponse::Ok().json(IssueStatusResponse {
+        issue_id,
+        title,
+        deliveries,
+    }))
+}
+
+async fn target_list_ids(lists: &[String], pool: &PgPool) -> Result<Vec<Uuid>, PublishError> {
+    let mut slugs = lists
+        .iter()
+        .map(|list| ListSlug::parse(list.clone()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(PublishError::ValidationError)?;
+    if slugs.is_empty() {
+        slugs.push(default_list_slug());
+    }
+    resolve_list_ids(pool, &slugs).await.map_err(|e| match e {
+        ListLookupError::UnknownList(_) => PublishError::ValidationError(e.to_string()),
+        ListLookupError::UnexpectedError(e) => PublishError::UnexpectedError(e),
+    })
+}
+
+// 标头是可选的，没有提供时请求不做幂等处理
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<IdempotencyKey>, PublishError> {
+    let header_value = match headers.get("Idempotency-Key") {
+        Some(header_value) => header_value,
+        None => return Ok(None),
+    };
+    let idempotency_key = header_value
+        .to_str()
+        .map_err(|_| {
+            PublishError::ValidationError(
+                "The 'Idempotency-Key' header was not a valid UTF8 string.".into(),
+            )
+        })?
+        .to_string()
+        .try_into()
+        .map_err(|e: anyhow::Error| PublishError::ValidationError(e.to_string()))?;
+    Ok(Some(idempotency_key))
+}
+
+// 优先使用 `Authorization: Bearer` 中的 API 令牌，否则退回到 HTTP Basic 用户名和密码
+async fn authenticate(
+    request: &HttpRequest,
+    required_scope: ApiTokenScope,
+    pool: &PgPool,
+    throttling_settings: &LoginThrottlingSettings,
+) -> Result<Uuid, PublishError> {
+    let map_auth_error = |e: AuthError| match e {
+        AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
+        AuthError::TooManyAttempts(retry_after) => PublishError::TooManyAttempts(retry_after),
+        AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
+    };
+
+    if let Some(token) = bearer_token(request.headers()).map_err(PublishError::AuthError)? {
+        let owner = validate_api_token(token, pool)
+            .await
+            .map_err(map_auth_error)?;
+        // 创建令牌之后用户的角色可能被降级，所以每次使用时都要重新检查
+        if !owner.scope.grants(required_scope) || !required_scope.is_allowed_for(owner.role) {
+            return Err(PublishError::Forbidden(anyhow::anyhow!(
+                "The API token does not grant the {} scope.",
+                required_scope
+            )));
         }
+        return Ok(owner.user_id);
     }
-    Ok(HttpResponse::Ok().finish())
+
+    let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
+    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let client_ip = request.peer_addr().map(|addr| addr.ip());
+    let user_id =
+        validate_credentials_with_throttling(credentials, client_ip, throttling_settings, pool)
+            .await
+            .map_err(map_auth_error)?;
+    // 用户名和密码没有范围限制，按角色判断能否执行这个操作
+    let role = get_user_role(user_id, pool)
+        .await
+        .map_err(PublishError::UnexpectedError)?;
+    if !required_scope.is_allowed_for(role) {
+        return Err(PublishError::Forbidden(anyhow::anyhow!(
+            "The {} role is not allowed to use the {} scope.",
+            role.as_str(),
+            required_scope
+        )));
+    }
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Get the role of a user", skip(pool))]
+async fn get_user_role(user_id: Uuid, pool: &PgPool) -> Result<Role, anyhow::Error> {
+    let row = sqlx::query!("SELECT role FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to retrieve the role of a user.")?;
+    Role::parse(&row.role).map_err(anyhow::Error::msg)
+}
+
+// 没有 `Bearer` 令牌时返回 `None`，由调用方尝试其他认证方式
+fn bearer_token(headers: &HeaderMap) -> Result<Option<Secret<String>>, anyhow::Error> {
+    let header_value = match headers.get("Authorization") {
+        Some(header_value) => header_value
+            .to_str()
+            .context("The 'Authorization' header was not a valid UTF8 string.")?,
+        None => return Ok(None),
+    };
+    Ok(header_value
+        .strip_prefix("Bearer ")
+        .map(|token| Secret::new(token.trim().to_string())))
 }
 
 fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
@@ -146,24 +360,3 @@ fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Erro
         password: Secret::new(password),
     })
 }
-
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let confirmed_subscribers = sqlx::query!(
-        r#"
-        SELECT email FROM subscriptions WHERE status = 'confirmed'
-        "#,
-    )
-    .fetch_all(pool)
-    .await?
-    .into_iter()
-    .map(|r| match SubscriberEmail::parse(r.email) {
-        Ok(email) => Ok(ConfirmedSubscriber { email }),
-        Err(error) => Err(anyhow::anyhow!(error)),
-    })
-    .collect();
-
-    Ok(confirmed_subscribers)
-}
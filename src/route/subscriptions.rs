@@ -1,6 +1,7 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::domain::{ListSlug, NewSubscriber, SubscriberEmail, SubscriberName};
 use crate::email_client::EmailClient;
 use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_lists::{default_list_slug, join_list, resolve_list_ids, ListLookupError};
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
@@ -157,6 +158,8 @@ impl ResponseError for SubscribeError {
 pub struct FormData {
     email: String,
     name: String,
+    // 要加入的列表，省略时加入默认列表
+    list: Option<String>,
 }
 
 // 实现TryFrom trait  将 FormData 转换为 NewSubscriber
@@ -186,16 +189,57 @@ pub async fn subscribe(
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let mut form = form.into_inner();
+    let list_slug = match form.list.take() {
+        Some(list) => ListSlug::parse(list).map_err(SubscribeError::ValidationError)?,
+        None => default_list_slug(),
+    };
+    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let list_id = match resolve_list_ids(&pool, &[list_slug]).await {
+        Ok(list_ids) => list_ids[0],
+        Err(e @ ListLookupError::UnknownList(_)) => {
+            return Err(SubscribeError::ValidationError(e.to_string()))
+        }
+        Err(ListLookupError::UnexpectedError(e)) => return Err(e.into()),
+    };
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let (subscriber_id, is_confirmed) =
+        match get_subscriber_by_email(&mut transaction, &new_subscriber.email)
+            .await
+            .context("Failed to look up an existing subscriber.")?
+        {
+            // 已确认的订阅者保持确认状态，但加入新列表仍然需要通过邮件确认
+            Some((subscriber_id, status)) if status == "confirmed" => (subscriber_id, true),
+            // 待确认（或已退订后重新订阅）：作废旧令牌，稍后签发新令牌并重新发送确认邮件
+            Some((subscriber_id, _)) => {
+                reset_pending_subscriber(&mut transaction, subscriber_id)
+                    .await
+                    .context("Failed to reset an existing subscriber.")?;
+                (subscriber_id, false)
+            }
+            None => (
+                insert_subscriber(&mut transaction, &new_subscriber)
+                    .await
+                    .context("Failed to insert new subscriber in the database.")?,
+                false,
+            ),
+        };
+    let is_member = join_list(&mut transaction, list_id, subscriber_id)
         .await
-        .context("Failed to insert new subscriber in the database.")?;
+        .context("Failed to add a subscriber to a list.")?;
+    if is_confirmed && is_member {
+        // 已经是该列表的成员：不重复发送邮件，也不暴露该地址已订阅
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a list membership.")?;
+        return Ok(HttpResponse::Ok().finish());
+    }
 
     let subscription_token = generate_subscription_token();
     store_token(&mut transaction, subscriber_id, &subscription_token)
@@ -229,16 +273,16 @@ pub async fn send_confirmation_email(
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token,
     );
 
-    // let plain_body = format!(
-    //     "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-    //     confirmation_link
-    // );
+    let plain_body = format!(
+        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
+        confirmation_link
+    );
 
     let html_body = format!(
         "Welcome to our newsletter!<br />\
@@ -249,7 +293,7 @@ pub async fn send_confirmation_email(
     // 向新订阅者发送一封（无用的）电子邮件
     // 我们暂时忽略电子邮件传递错误
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &"outbound")
+        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
         .await
 }
 
@@ -283,6 +327,47 @@ pub async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
+#[tracing::instrument(name = "Get subscriber by email", skip(email, transaction))]
+pub async fn get_subscriber_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, status FROM subscriptions WHERE email = $1 FOR UPDATE
+        "#,
+        email.as_ref(),
+    )
+    .fetch_optional(transaction.acquire().await?)
+    .await?;
+    Ok(row.map(|r| (r.id, r.status)))
+}
+
+// 让订阅者回到待确认状态，并删除其所有旧的确认令牌
+#[tracing::instrument(name = "Reset a subscriber to pending confirmation", skip(transaction))]
+pub async fn reset_pending_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'pending_confirmation' WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .execute(transaction.acquire().await?)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens WHERE subscriber_id = $1
+        "#,
+        subscriber_id,
+    )
+    .execute(transaction.acquire().await?)
+    .await?;
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(subscription_token, transaction)
@@ -295,7 +380,8 @@ pub async fn store_token(
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
         r#"
-        INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        VALUES ($1, $2, now())
         "#,
         subscription_token,
         subscriber_id,
@@ -312,7 +398,7 @@ pub async fn store_token(
 }
 
 /// 生成一个随机的 25 个字符长 区分大小写的订阅令牌
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
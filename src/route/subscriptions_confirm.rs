@@ -1,5 +1,13 @@
-use actix_web::{web, HttpResponse};
-use sqlx::PgPool;
+use crate::route::error_chain_fmt;
+use crate::startup::SubscriptionTokenTtl;
+use crate::subscriber_lists::confirm_list_memberships;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
 use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
@@ -7,33 +15,153 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("The subscription token is unknown.")]
+    UnknownToken,
+    #[error("The confirmation link has expired.")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmError::UnknownToken => StatusCode::NOT_FOUND,
+            ConfirmError::ExpiredToken => StatusCode::GONE,
+            ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            // 告诉订阅者如何获取新的链接，而不是只返回一个空白的错误页
+            ConfirmError::ExpiredToken => HttpResponse::build(self.status_code())
+                .content_type(ContentType::html())
+                .body(confirmation_page(
+                    "Link expired",
+                    "This confirmation link has expired.",
+                    "Please subscribe again to receive a new confirmation email.",
+                )),
+            _ => HttpResponse::new(self.status_code()),
+        }
+    }
+}
+
+struct ConfirmationToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+    status: String,
+}
+
+/// 确认状态机：
+/// - 未知的令牌，或订阅者既没有确认也不在待确认状态 -> 404
+/// - 令牌已被使用：订阅者已经确认过 -> 幂等地返回“已确认”页面，否则 -> 404
+/// - 令牌未使用且未过期 -> 在同一个事务中确认订阅者和待确认的列表成员关系，并标记令牌已使用；
+///   已确认的订阅者加入新列表时收到的也是这种令牌
+/// - 令牌未使用但已过期 -> 410
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, subscription_token_ttl)
+)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    subscription_token_ttl: web::Data<SubscriptionTokenTtl>,
+) -> Result<HttpResponse, ConfirmError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let token = get_confirmation_token(&mut transaction, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve the subscriber associated with the provided token.")?
+        .ok_or(ConfirmError::UnknownToken)?;
 
-    match id {
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
-            }
-            HttpResponse::Ok().finish()
+    if token.status != "confirmed" && token.status != "pending_confirmation" {
+        return Err(ConfirmError::UnknownToken);
+    }
+    if token.used_at.is_some() {
+        if token.status == "confirmed" {
+            return Ok(html_response(confirmation_page(
+                "Already confirmed",
+                "Your subscription has already been confirmed.",
+                "There is nothing else to do - see you in your inbox!",
+            )));
         }
-        None => HttpResponse::Unauthorized().finish(),
-    };
+        return Err(ConfirmError::UnknownToken);
+    }
+
+    let ttl = chrono::Duration::from_std(subscription_token_ttl.0)
+        .context("The subscription token TTL is out of range.")?;
+    if token.created_at + ttl < Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
 
-    HttpResponse::Ok().finish()
+    confirm_subscriber(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to update the subscriber status to `confirmed`.")?;
+    confirm_list_memberships(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to confirm the list memberships of a subscriber.")?;
+    mark_token_as_used(&mut transaction, &parameters.subscription_token)
+        .await
+        .context("Failed to mark the subscription token as used.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+
+    Ok(html_response(confirmation_page(
+        "Subscription confirmed",
+        "Thank you for confirming your subscription!",
+        "You will receive our next newsletter issue in your inbox.",
+    )))
+}
+
+fn html_response(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body)
+}
+
+fn confirmation_page(title: &str, headline: &str, details: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <p>{headline}</p>
+    <p>{details}</p>
+</body>
+</html>"#,
+    )
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+#[tracing::instrument(
+    name = "Mark subscriber as confirmed",
+    skip(subscriber_id, transaction)
+)]
+pub async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
         subscriber_id
     )
-    .execute(pool)
+    .execute(transaction.deref_mut())
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
@@ -42,20 +170,45 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
     Ok(())
 }
 
-#[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-pub async fn get_subscriber_id_from_token(
-    pool: &PgPool,
+#[tracing::instrument(name = "Mark subscription token as used", skip_all)]
+async fn mark_token_as_used(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscription_tokens SET used_at = now() WHERE subscription_token = $1"#,
+        subscription_token
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
+
+// 锁住订阅者的行，避免同一链接被并发点击时重复确认
+#[tracing::instrument(
+    name = "Get subscriber from token",
+    skip(subscription_token, transaction)
+)]
+async fn get_confirmation_token(
+    transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+) -> Result<Option<ConfirmationToken>, sqlx::Error> {
+    let token = sqlx::query_as!(
+        ConfirmationToken,
+        r#"
+        SELECT t.subscriber_id, t.created_at, t.used_at, s.status
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1
+        FOR UPDATE OF s
+        "#,
         subscription_token,
     )
-    .fetch_optional(pool)
+    .fetch_optional(transaction.deref_mut())
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(token)
 }
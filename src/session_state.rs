@@ -1,14 +1,28 @@
 use actix_session::{Session, SessionExt};
 use actix_web::dev::Payload;
 use actix_web::{FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
 use serde::de::Error;
 use std::future::{ready, Ready};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
+/// 密码已经验证通过、但还没有完成两步验证的登录
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct PendingSecondFactor {
+    pub user_id: Uuid,
+    pub password_verified_at: DateTime<Utc>,
+    // 在这个会话中输错验证码的次数
+    #[serde(default)]
+    pub failed_attempts: u32,
+}
+
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const PENDING_SECOND_FACTOR_KEY: &'static str = "pending_second_factor";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -26,6 +40,84 @@ impl TypedSession {
             .map_err(|e| serde_json::Error::custom(format!("Session get error: {}", e)))
     }
 
+    // 记录登录时间，用于判断会话是否在用户重置密码之前建立
+    pub fn insert_logged_in_at(
+        &self,
+        logged_in_at: DateTime<Utc>,
+    ) -> Result<(), serde_json::Error> {
+        self.0
+            .insert(Self::LOGGED_IN_AT_KEY, logged_in_at)
+            .map_err(|e| serde_json::Error::custom(format!("Session insert error: {}", e)))
+    }
+
+    pub fn get_logged_in_at(&self) -> Result<Option<DateTime<Utc>>, serde_json::Error> {
+        self.0
+            .get(Self::LOGGED_IN_AT_KEY)
+            .map_err(|e| serde_json::Error::custom(format!("Session get error: {}", e)))
+    }
+
+    // 完成登录：轮换会话 id 防止会话固定攻击，并清除可能残留的两步验证状态
+    pub fn log_in(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+        self.renew();
+        self.remove_pending_second_factor();
+        self.insert_user_id(user_id)?;
+        self.insert_logged_in_at(Utc::now())
+    }
+
+    // 此时会话中没有 `user_id`，`reject_anonymous_users` 不会放行任何 `/admin/*` 请求
+    pub fn insert_pending_second_factor(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+        let pending = PendingSecondFactor {
+            user_id,
+            password_verified_at: Utc::now(),
+            failed_attempts: 0,
+        };
+        self.0.remove(Self::USER_ID_KEY);
+        self.0
+            .insert(Self::PENDING_SECOND_FACTOR_KEY, pending)
+            .map_err(|e| serde_json::Error::custom(format!("Session insert error: {}", e)))
+    }
+
+    pub fn get_pending_second_factor(
+        &self,
+    ) -> Result<Option<PendingSecondFactor>, serde_json::Error> {
+        self.0
+            .get(Self::PENDING_SECOND_FACTOR_KEY)
+            .map_err(|e| serde_json::Error::custom(format!("Session get error: {}", e)))
+    }
+
+    // 记录一次输错的验证码，返回累计的失败次数
+    pub fn record_second_factor_failure(
+        &self,
+        mut pending: PendingSecondFactor,
+    ) -> Result<u32, serde_json::Error> {
+        pending.failed_attempts += 1;
+        self.0
+            .insert(Self::PENDING_SECOND_FACTOR_KEY, pending)
+            .map_err(|e| serde_json::Error::custom(format!("Session insert error: {}", e)))?;
+        Ok(pending.failed_attempts)
+    }
+
+    pub fn remove_pending_second_factor(&self) {
+        self.0.remove(Self::PENDING_SECOND_FACTOR_KEY);
+    }
+
+    // 启用两步验证时，新生成的密钥在用户输入第一个验证码之前只保存在会话中
+    pub fn insert_pending_totp_secret(&self, secret: &str) -> Result<(), serde_json::Error> {
+        self.0
+            .insert(Self::PENDING_TOTP_SECRET_KEY, secret)
+            .map_err(|e| serde_json::Error::custom(format!("Session insert error: {}", e)))
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<String>, serde_json::Error> {
+        self.0
+            .get(Self::PENDING_TOTP_SECRET_KEY)
+            .map_err(|e| serde_json::Error::custom(format!("Session get error: {}", e)))
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
@@ -1,4 +1,4 @@
-use crate::authentication::reject_anonymous_users;
+use crate::authentication::{reject_anonymous_users, reject_non_owners, reject_viewers};
 use crate::configuration::{DatabaseSettings, Settings};
 use crate::email_client::EmailClient;
 use crate::route::*;
@@ -14,6 +14,7 @@ use actix_web_lab::middleware::from_fn;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use std::net::TcpListener;
+use std::time::Duration;
 use tracing_actix_web::TracingLogger;
 
 // 新类型，用于保存新建的服务器及其端口
@@ -26,34 +27,17 @@ impl Application {
     // 我们已经将 `build` 函数转换为 `Application` 的构造函数
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         // 拿到pgsql的连接
-        let connection_pool = get_connection_pool(&configuration.database);
+        let connection_pool = get_connection_pool(&configuration.database).await;
+        if configuration.database.migrate_on_startup {
+            run_migrations(&connection_pool).await?;
+        }
 
-        // 使用 `configuration` 构建 `EmailClient`
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address");
-
-        let timeout = configuration.email_client.timeout();
-
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
-            timeout,
-        );
+        // 使用 `configuration` 构建 `EmailClient`，具体的发送后端由配置决定
+        let email_client = configuration.email_client.client();
 
         let listener = TcpListener::bind("127.0.0.1:0")?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
-            connection_pool.await,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
 
         // 我们将绑定的端口“保存”在 `Application` 的一个字段中
         Ok(Self { port, server })
@@ -79,31 +63,58 @@ pub async fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     // .connect_lazy_with(configuration.with_db())
 }
 
+// 执行 `migrations/` 中尚未应用的迁移，迁移文件在编译时被嵌入二进制文件
+// sqlx 会先获取一个数据库咨询锁，所以多个实例同时启动也是安全的
+pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    sqlx::migrate!("./migrations").run(pool).await
+}
+
 // 我们需要定义一个包装器类型，以便在 `subscribe` 处理程序中检索 URL
 // 在 actix-web 中，从上下文中检索是基于类型的：使用
 // 原始 `String` 会让我们面临冲突。
 pub struct ApplicationBaseUrl(pub String);
 
+// 确认令牌的有效期，同样用包装器类型避免与其他 `Duration` 冲突
+pub struct SubscriptionTokenTtl(pub Duration);
+
+// 重置密码令牌的有效期
+pub struct PasswordResetTokenTtl(pub Duration);
+
+// 邀请新用户时设置密码链接的有效期
+pub struct InvitationTokenTtl(pub Duration);
+
 // 注意不同的签名！
 // 我们在快乐路径上返回 `Server`，并且删除了 `async` 关键字
 // 我们没有 .await 调用，因此不再需要它。
+// 各项设置从 `configuration` 中取出，避免随着功能增加不断追加位置参数
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
     // 将连接包装在智能指针中
     let db_pool = web::Data::new(db_pool);
     let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let subscription_token_ttl = Data::new(SubscriptionTokenTtl(
+        configuration.application.subscription_token_ttl(),
+    ));
+    let password_reset_token_ttl = Data::new(PasswordResetTokenTtl(
+        configuration.application.password_reset_token_ttl(),
+    ));
+    let invitation_token_ttl = Data::new(InvitationTokenTtl(
+        configuration.application.invitation_token_ttl(),
+    ));
+    let base_url = Data::new(ApplicationBaseUrl(configuration.application.base_url));
+    let idempotency_settings = Data::new(configuration.idempotency);
+    let login_throttling_settings = Data::new(configuration.login_throttling);
+    let feed_settings = Data::new(configuration.feed);
+    let hmac_secret = configuration.application.hmac_secret;
 
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_store = RedisSessionStore::new(configuration.redis_uri.expose_secret()).await?;
 
     let server = HttpServer::new(move || {
         App::new()
@@ -120,23 +131,159 @@ async fn run(
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(newsletter::publish_newsletter))
+                    // 所有修改期刊或投递的路由都不对 viewer 开放
+                    .route(
+                        "/newsletters",
+                        web::post()
+                            .to(newsletter::publish_newsletter)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route(
+                        "/newsletters/scheduled",
+                        web::get().to(scheduled_newsletters),
+                    )
+                    .route(
+                        "/newsletters/scheduled/{issue_id}/reschedule",
+                        web::post()
+                            .to(reschedule_newsletter)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route(
+                        "/newsletters/scheduled/{issue_id}/cancel",
+                        web::post()
+                            .to(cancel_scheduled_newsletter)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route("/newsletters/history", web::get().to(newsletter_history))
+                    .route("/newsletters/drafts", web::get().to(list_drafts))
+                    .route(
+                        "/newsletters/drafts",
+                        web::post()
+                            .to(create_draft)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route("/newsletters/drafts/{issue_id}", web::get().to(edit_draft))
+                    .route(
+                        "/newsletters/drafts/{issue_id}",
+                        web::post().to(save_draft).wrap(from_fn(reject_viewers)),
+                    )
+                    .route(
+                        "/newsletters/drafts/{issue_id}/autosave",
+                        web::post()
+                            .to(autosave_draft)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route(
+                        "/newsletters/drafts/{issue_id}/delete",
+                        web::post()
+                            .to(delete_draft)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route("/newsletters/preview", web::post().to(preview_newsletter))
+                    .route(
+                        "/newsletters/test",
+                        web::post()
+                            .to(send_test_newsletter)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    // 必须注册在 `/newsletters` 下其他固定路径之后，否则 `scheduled`、`drafts` 等会被当作期刊 id
+                    .route(
+                        "/newsletters/{issue_id}",
+                        web::get().to(newsletter_issue_deliveries),
+                    )
+                    .route("/delivery-failures", web::get().to(delivery_failures))
+                    .route(
+                        "/delivery-failures/requeue",
+                        web::post()
+                            .to(requeue_delivery_failure)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(reject_non_owners))
+                            .route("", web::get().to(list_users))
+                            .route("", web::post().to(invite_user))
+                            .route("/{user_id}/role", web::post().to(change_user_role))
+                            .route("/{user_id}/deactivate", web::post().to(deactivate_user)),
+                    )
+                    .route("/subscribers", web::get().to(list_subscribers))
+                    // 必须注册在 `/subscribers/{subscriber_id}` 之前
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(import_subscribers_form),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::post()
+                            .to(import_subscribers)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .service(
+                        web::scope("/subscribers/{subscriber_id}")
+                            .wrap(from_fn(reject_viewers))
+                            .route("/confirm", web::post().to(confirm_subscriber_manually))
+                            .route("/unsubscribe", web::post().to(unsubscribe_subscriber))
+                            .route("/delete", web::post().to(delete_subscriber))
+                            .route(
+                                "/resend-confirmation",
+                                web::post().to(resend_confirmation_email),
+                            ),
+                    )
+                    .route("/lists", web::get().to(list_subscriber_lists))
+                    .route(
+                        "/lists",
+                        web::post()
+                            .to(create_list)
+                            .wrap(from_fn(reject_viewers)),
+                    )
+                    .route("/api-tokens", web::get().to(list_api_tokens))
+                    .route("/api-tokens", web::post().to(create_api_token))
+                    .route(
+                        "/api-tokens/{api_token_id}/revoke",
+                        web::post().to(revoke_api_token),
+                    )
+                    .route("/two-factor", web::get().to(two_factor_settings))
+                    .route("/two-factor", web::post().to(confirm_two_factor))
+                    .route("/two-factor/disable", web::post().to(remove_two_factor))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
                     .route("/logout", web::post().to(log_out)),
             )
             .route("/login", web::get().to(login_from))
             .route("/login", web::post().to(login))
+            .route("/login/two-factor", web::get().to(login_second_factor_form))
+            .route("/login/two-factor", web::post().to(login_second_factor))
+            .route("/login/forgot", web::get().to(forgot_password_form))
+            .route("/login/forgot", web::post().to(request_password_reset))
+            .route("/login/reset", web::get().to(reset_password_form))
+            .route("/login/reset", web::post().to(reset_password))
             .route("/health_check", web::get().to(health_check))
+            .route("/archive", web::get().to(archive))
+            .route("/archive/{issue_id}", web::get().to(archived_issue))
+            .route("/feed.atom", web::get().to(atom_feed))
+            .route("/feed.rss", web::get().to(rss_feed))
             // 我们的路由表中为 POST /subscribe 请求添加一个新条目
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route("/subscriptions/unsubscribe", web::get().to(unsubscribe_form))
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
             .route("/newsletters", web::post().to(newsletters::publish_newsletter))
+            .route(
+                "/newsletters/{issue_id}",
+                web::get().to(newsletters::newsletter_issue_status),
+            )
 
             // 将数据库连接注册为应用程序状态的一部分
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(subscription_token_ttl.clone())
+            .app_data(password_reset_token_ttl.clone())
+            .app_data(invitation_token_ttl.clone())
+            .app_data(idempotency_settings.clone())
+            .app_data(login_throttling_settings.clone())
+            .app_data(feed_settings.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
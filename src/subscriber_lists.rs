@@ -0,0 +1,130 @@
+use crate::domain::ListSlug;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+/// 迁移时创建的默认列表，没有指定列表的订阅和期刊都归入这里
+pub const DEFAULT_LIST_SLUG: &str = "newsletter";
+
+pub struct SubscriberList {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ListLookupError {
+    #[error("There is no list called {0}.")]
+    UnknownList(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+pub fn default_list_slug() -> ListSlug {
+    ListSlug::parse(DEFAULT_LIST_SLUG.to_string()).unwrap()
+}
+
+#[tracing::instrument(name = "Get subscriber lists", skip(pool))]
+pub async fn get_subscriber_lists(pool: &PgPool) -> Result<Vec<SubscriberList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        SubscriberList,
+        r#"SELECT list_id, slug, name FROM subscriber_lists ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber lists.")?;
+    Ok(lists)
+}
+
+/// 把列表标识解析为 id，任何一个不存在都返回 `ListLookupError::UnknownList`
+#[tracing::instrument(name = "Resolve subscriber lists", skip(pool))]
+pub async fn resolve_list_ids(
+    pool: &PgPool,
+    slugs: &[ListSlug],
+) -> Result<Vec<Uuid>, ListLookupError> {
+    let slugs: Vec<String> = slugs.iter().map(|s| s.as_ref().to_string()).collect();
+    let lists = sqlx::query!(
+        r#"SELECT list_id, slug FROM subscriber_lists WHERE slug = ANY($1)"#,
+        &slugs
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up subscriber lists.")?;
+
+    if let Some(unknown) = slugs
+        .iter()
+        .find(|slug| !lists.iter().any(|l| &l.slug == *slug))
+    {
+        return Err(ListLookupError::UnknownList(unknown.clone()));
+    }
+    Ok(lists.into_iter().map(|l| l.list_id).collect())
+}
+
+/// 创建一个新列表，标识已被占用时返回 `false`
+#[tracing::instrument(name = "Create subscriber list", skip(pool))]
+pub async fn create_subscriber_list(
+    pool: &PgPool,
+    slug: &ListSlug,
+    name: &str,
+) -> Result<bool, anyhow::Error> {
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_lists (list_id, slug, name)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create a subscriber list.")?
+    .rows_affected();
+    Ok(n_inserted_rows == 1)
+}
+
+/// 把订阅者以待确认的状态加入列表，已经是成员时保持原来的状态
+///
+/// 返回成员关系是否已经确认过。
+#[tracing::instrument(name = "Join subscriber list", skip(transaction))]
+pub async fn join_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    // 空操作的 DO UPDATE 让 RETURNING 在冲突时也能返回已有的状态
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        VALUES ($1, $2, 'pending_confirmation')
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE SET status = list_memberships.status
+        RETURNING status
+        "#,
+        list_id,
+        subscriber_id
+    )
+    .fetch_one(transaction.deref_mut())
+    .await?;
+    Ok(row.status == "confirmed")
+}
+
+/// 订阅者点击确认链接（或被管理员手动确认）后，待确认的成员关系全部生效
+#[tracing::instrument(name = "Confirm list memberships", skip(transaction))]
+pub async fn confirm_list_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'confirmed'
+        WHERE subscriber_id = $1 AND status = 'pending_confirmation'
+        "#,
+        subscriber_id
+    )
+    .execute(transaction.deref_mut())
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    TestApp, TestUser,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+// 直接写入数据库，方便控制订阅时间和状态
+async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    name: &str,
+    status: &str,
+    days_ago: i64,
+) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        subscriber_id,
+        email,
+        name,
+        Utc::now() - Duration::days(days_ago),
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+async fn subscriber_status(app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_admin_subscribers("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_listed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    insert_subscriber(&app, "ursula@example.com", "Ursula Le Guin", "confirmed", 0).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_subscribers_html("").await;
+
+    assert!(html_page.contains("<td>ursula@example.com</td>"));
+    assert!(html_page.contains("<td>Ursula Le Guin</td>"));
+    assert!(html_page.contains("(2 subscribers)"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@example.com", "Ursula Le Guin", "confirmed", 0).await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "Octavia Butler",
+        "confirmed",
+        0,
+    )
+    .await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_subscribers_html("q=URSULA").await;
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(!html_page.contains("octavia@example.com"));
+
+    let html_page = app.get_admin_subscribers_html("q=butler").await;
+    assert!(html_page.contains("octavia@example.com"));
+    assert!(!html_page.contains("ursula@example.com"));
+
+    // 通配符按字面匹配
+    let html_page = app.get_admin_subscribers_html("q=%25").await;
+    assert!(html_page.contains("(0 subscribers)"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_subscription_date() {
+    let app = spawn_app().await;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "Pending",
+        "pending_confirmation",
+        0,
+    )
+    .await;
+    insert_subscriber(&app, "recent@example.com", "Recent", "confirmed", 1).await;
+    insert_subscriber(&app, "old@example.com", "Old", "confirmed", 30).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app
+        .get_admin_subscribers_html("status=pending_confirmation")
+        .await;
+    assert!(html_page.contains("pending@example.com"));
+    assert!(!html_page.contains("recent@example.com"));
+
+    let from = (Utc::now() - Duration::days(7)).date_naive();
+    let html_page = app
+        .get_admin_subscribers_html(&format!("status=confirmed&subscribed_from={}", from))
+        .await;
+    assert!(html_page.contains("recent@example.com"));
+    assert!(!html_page.contains("old@example.com"));
+    assert!(!html_page.contains("pending@example.com"));
+
+    let to = (Utc::now() - Duration::days(7)).date_naive();
+    let html_page = app
+        .get_admin_subscribers_html(&format!("subscribed_to={}", to))
+        .await;
+    assert!(html_page.contains("old@example.com"));
+    assert!(!html_page.contains("recent@example.com"));
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for query in ["status=gone", "subscribed_from=yesterday"] {
+        let response = app.get_admin_subscribers(query).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The filter {} was not rejected.",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated() {
+    let app = spawn_app().await;
+    for i in 0..55 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber-{:02}@example.com", i),
+            "Subscriber",
+            "confirmed",
+            i,
+        )
+        .await;
+    }
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_subscribers_html("status=confirmed").await;
+    assert!(html_page.contains("Page 1 of 2 (55 subscribers)"));
+    assert!(html_page.contains("subscriber-00@example.com"));
+    assert!(!html_page.contains("subscriber-54@example.com"));
+    assert!(
+        html_page.contains(r#"<a href="/admin/subscribers?page=2&amp;status=confirmed">Next</a>"#)
+    );
+
+    let html_page = app
+        .get_admin_subscribers_html("page=2&status=confirmed")
+        .await;
+    assert!(html_page.contains("Page 2 of 2 (55 subscribers)"));
+    assert!(html_page.contains("subscriber-54@example.com"));
+    assert!(!html_page.contains("subscriber-00@example.com"));
+}
+
+#[tokio::test]
+async fn a_pending_subscriber_can_be_confirmed_manually() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "pending@example.com",
+        "Pending",
+        "pending_confirmation",
+        0,
+    )
+    .await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_subscriber_action(subscriber_id, "confirm").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    let html_page = app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been confirmed.</i></p>"));
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("confirmed")
+    );
+
+    app.post_subscriber_action(subscriber_id, "confirm").await;
+    let html_page = app.get_admin_subscribers_html("").await;
+    assert!(html_page
+        .contains("<p><i>The subscriber does not exist or is not pending confirmation.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_unsubscribed_manually() {
+    let app = spawn_app().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "Ursula", "confirmed", 0).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("unsubscribed")
+    );
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_deleted() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+
+    let response = app.post_subscriber_action(subscriber_id, "delete").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    let html_page = app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been deleted.</i></p>"));
+    assert_eq!(subscriber_status(&app, subscriber_id).await, None);
+    let n_tokens = sqlx::query!(r#"SELECT count(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 0);
+}
+
+#[tokio::test]
+async fn the_confirmation_email_can_be_resent_with_a_new_link() {
+    let app = spawn_app().await;
+    let old_links = create_unconfirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriber_action(subscriber_id, "resend-confirmation")
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let new_links = app.get_confirmation_links(&email_request);
+    assert_ne!(old_links.html, new_links.html);
+
+    reqwest::get(new_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[tokio::test]
+async fn confirmation_emails_are_not_resent_to_confirmed_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "Ursula", "confirmed", 0).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriber_action(subscriber_id, "resend-confirmation")
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+}
+
+#[tokio::test]
+async fn viewers_can_see_but_not_change_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "Ursula", "confirmed", 0).await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let html_page = app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("ursula@example.com"));
+
+    for action in ["confirm", "unsubscribe", "delete", "resend-confirmation"] {
+        let response = app.post_subscriber_action(subscriber_id, action).await;
+        assert_eq!(response.status().as_u16(), 403);
+    }
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("confirmed")
+    );
+}
@@ -0,0 +1,247 @@
+use crate::helpers::{spawn_app, TestApp, TestUser};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const CSV: &str = "\
+Email,Name,Source
+ursula@example.com,Ursula Le Guin,old platform
+octavia@example.com,Octavia Butler,old platform
+not-an-email,Nobody,old platform
+ursula@example.com,Ursula again,old platform
+invalid-name@example.com,Bad {name},old platform
+";
+
+async fn subscribers(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.email, r.status))
+        .collect()
+}
+
+#[tokio::test]
+async fn a_dry_run_reports_errors_without_saving_anything() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_import_subscribers(CSV, "confirmed", true).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Dry run: 2 subscribers would be imported as confirmed."));
+    assert!(html_page.contains("3 rows have errors"));
+    assert!(html_page.contains("<td>4</td>"));
+    assert!(html_page.contains("not-an-email is not a valid subscriber email."));
+    assert!(html_page.contains("The email already appears on an earlier row."));
+    assert!(html_page.contains("Bad {name} is not a valid subscriber name."));
+    assert!(subscribers(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_imported_as_confirmed_without_emails() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_import_subscribers(CSV, "confirmed", false).await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("2 subscribers have been imported as confirmed."));
+    assert_eq!(
+        subscribers(&app).await,
+        vec![
+            ("octavia@example.com".to_string(), "confirmed".to_string()),
+            ("ursula@example.com".to_string(), "confirmed".to_string()),
+        ]
+    );
+    let n_members = sqlx::query!(r#"SELECT count(*) AS "count!" FROM list_memberships"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_members, 2);
+}
+
+#[tokio::test]
+async fn imported_subscribers_can_be_sent_a_confirmation_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_import_subscribers(CSV, "send_confirmation", false)
+        .await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(
+        "2 subscribers have been imported. \
+        Their confirmation emails are being sent in the background."
+    ));
+    // 导入请求本身不发送邮件
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+    app.dispatch_all_pending_confirmation_emails().await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let statuses: Vec<String> = subscribers(&app).await.into_iter().map(|s| s.1).collect();
+    assert!(statuses.contains(&"confirmed".to_string()));
+    assert!(statuses.contains(&"pending_confirmation".to_string()));
+}
+
+#[tokio::test]
+async fn existing_subscribers_are_reported_and_left_untouched() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_import_subscribers(CSV, "confirmed", false).await;
+
+    let response = app
+        .post_import_subscribers(
+            "email,name\nursula@example.com,Ursula\nmargaret@example.com,Margaret Atwood\n",
+            "confirmed",
+            false,
+        )
+        .await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("1 subscribers have been imported as confirmed."));
+    assert!(html_page.contains("The email is already subscribed."));
+    assert_eq!(subscribers(&app).await.len(), 3);
+}
+
+#[tokio::test]
+async fn a_file_without_the_expected_columns_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_import_subscribers(
+            "address,full name\nursula@example.com,Ursula\n",
+            "confirmed",
+            true,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn viewers_cannot_import_subscribers() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app.post_import_subscribers(CSV, "confirmed", false).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(subscribers(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_csv() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_import_subscribers(CSV, "confirmed", false).await;
+
+    let response = app.get_export_subscribers().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+
+    let body = response.text().await.unwrap();
+    let mut lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.remove(0), "email,name,status,subscribed_at");
+    lines.sort();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("octavia@example.com,Octavia Butler,confirmed,"));
+    assert!(lines[1].starts_with("ursula@example.com,Ursula Le Guin,confirmed,"));
+}
+
+#[tokio::test]
+async fn an_export_can_be_imported_again() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_import_subscribers(CSV, "confirmed", false).await;
+    let export = app.get_export_subscribers().await.text().await.unwrap();
+    sqlx::query!("DELETE FROM list_memberships")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("DELETE FROM subscriptions")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app
+        .post_import_subscribers(&export, "confirmed", false)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("2 subscribers have been imported as confirmed."));
+}
+
+#[tokio::test]
+async fn failed_confirmation_emails_are_retried_in_the_background() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_import_subscribers(
+        "email,name\nursula@example.com,Ursula Le Guin\n",
+        "send_confirmation",
+        false,
+    )
+    .await;
+
+    app.dispatch_all_pending_confirmation_emails().await;
+    // 失败的邮件按退避时间重新调度，这里直接让它到期
+    sqlx::query!("UPDATE confirmation_email_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_confirmation_emails().await;
+
+    let n_queued = sqlx::query!(r#"SELECT count(*) AS "count!" FROM confirmation_email_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+}
@@ -0,0 +1,209 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn store_and_login(app: &TestApp, role: &str) -> TestUser {
+    let user = TestUser::generate_with_role(role);
+    user.store(&app.db_pool).await;
+    user.login(app).await;
+    user
+}
+
+#[tokio::test]
+async fn viewers_can_open_the_dashboard_but_cannot_publish() {
+    let app = spawn_app().await;
+    let viewer = store_and_login(&app, "viewer").await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", viewer.username)));
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "Newsletter body as plain text",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() {
+    let app = spawn_app().await;
+    store_and_login(&app, "editor").await;
+
+    let response = app.get_admin_users().await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_deactivate_user(app.test_user.user_id).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_invited_user_sets_a_password_through_the_emailed_link() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let username = Uuid::new_v4().to_string();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": &username,
+            "email": format!("{}@example.com", Uuid::new_v4()),
+            "role": "editor",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains(&format!(
+        "<p><i>{} has been invited as editor.</i></p>",
+        username
+    )));
+
+    // 邀请邮件中只有一次性的设置密码链接，没有任何密码
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(!body["HtmlBody"].as_str().unwrap().contains("password:"));
+    assert!(!body["TextBody"].as_str().unwrap().contains("password:"));
+    let invite_link = app.get_confirmation_links(email_request).html;
+    assert_eq!(invite_link.path(), "/login/reset");
+    let token = invite_link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    app.post_logout().await;
+    let response = reqwest::get(invite_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let password = Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": &password,
+            "new_password_check": &password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &username,
+            "password": &password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Your role: editor"));
+}
+
+#[tokio::test]
+async fn inviting_an_existing_username_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": &app.test_user.username,
+            "email": format!("{}@example.com", Uuid::new_v4()),
+            "role": "viewer",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("<p><i>A user with this username or email already exists.</i></p>"));
+}
+
+#[tokio::test]
+async fn an_owner_can_change_the_role_of_a_user() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_change_user_role(viewer.user_id, "editor").await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("<p><i>The role has been changed to editor.</i></p>"));
+    let saved = sqlx::query!("SELECT role FROM users WHERE user_id = $1", viewer.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the user.");
+    assert_eq!(saved.role, "editor");
+}
+
+#[tokio::test]
+async fn owners_cannot_change_their_own_role_or_deactivate_themselves() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_change_user_role(app.test_user.user_id, "viewer")
+        .await;
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot change your own role.</i></p>"));
+
+    app.post_deactivate_user(app.test_user.user_id).await;
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot deactivate yourself.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_deactivated_user_cannot_log_in() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_deactivate_user(editor.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been deactivated.</i></p>"));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &editor.username,
+            "password": &editor.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deactivating_a_user_ends_their_existing_session() {
+    let app = spawn_app().await;
+    let editor = store_and_login(&app, "editor").await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    sqlx::query!(
+        "UPDATE users SET is_active = false WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to deactivate the user.");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
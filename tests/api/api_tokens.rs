@@ -0,0 +1,185 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+    TestUser,
+};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+// 在后台创建一个令牌，并从只展示一次的页面中取出令牌明文
+async fn create_token(app: &TestApp, scope: &str) -> String {
+    let response = app.post_create_api_token("ci", scope).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let start = html_page.find("<p><code>").unwrap() + "<p><code>".len();
+    let end = start + html_page[start..].find("</code>").unwrap();
+    html_page[start..end].to_string()
+}
+
+async fn api_token_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT api_token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .api_token_id
+}
+
+#[tokio::test]
+async fn a_created_token_is_shown_once_and_listed_by_its_prefix() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let token = create_token(&app, "newsletters:publish").await;
+    assert!(token.starts_with("z2p_"));
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains(&token[..12]));
+    assert!(!html_page.contains(&token));
+}
+
+#[tokio::test]
+async fn a_publish_token_can_publish_newsletters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:publish").await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters_with_token(&token, newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn a_read_only_token_can_check_the_status_of_an_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:read").await;
+    let response = app.post_newsletters(newsletter_body()).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    let response = app
+        .api_client
+        .get(body["status_url"].as_str().unwrap())
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_newsletters_with_token(&format!("z2p_{}", Uuid::new_v4()), newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:publish").await;
+
+    let response = app.post_revoke_api_token(api_token_id(&app).await).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token has been revoked.</i></p>"));
+
+    let response = app
+        .post_newsletters_with_token(&token, newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_read_only_token_cannot_publish() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:read").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters_with_token(&token, newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn viewers_cannot_create_publish_tokens() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app.post_create_api_token("ci", "newsletters:publish").await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page
+        .contains("<p><i>Your role does not allow creating newsletters:publish tokens.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_token_stops_publishing_once_its_owner_is_demoted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:publish").await;
+
+    sqlx::query!(
+        "UPDATE users SET role = 'viewer' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .post_newsletters_with_token(&token, newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn users_cannot_revoke_tokens_of_other_users() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = create_token(&app, "newsletters:publish").await;
+    app.post_logout().await;
+
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    other_user.login(&app).await;
+    let response = app.post_revoke_api_token(api_token_id(&app).await).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+
+    let response = app
+        .post_newsletters_with_token(&token, newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
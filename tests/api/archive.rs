@@ -0,0 +1,124 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::Mock;
+
+async fn publish_an_issue(app: &TestApp, title: &str, scheduled_for: Option<String>) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": title,
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "scheduled_for": scheduled_for.unwrap_or_default(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_in_the_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_an_issue(&app, "Published <title>", None).await;
+
+    let html_page = app.get_archive_html().await;
+
+    assert!(html_page.contains(&format!(
+        r#"<a href="/archive/{}">Published &lt;title&gt;</a> - {}"#,
+        issue_id,
+        Utc::now().format("%Y-%m-%d")
+    )));
+}
+
+#[tokio::test]
+async fn an_archived_issue_renders_its_content() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_an_issue(&app, "Published title", None).await;
+
+    let response = app.get_archived_issue(issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Published title</h1>"));
+    assert!(html_page.contains("<p>Newsletter body as HTML</p>"));
+    assert!(html_page.contains(&format!("Published on {}", Utc::now().format("%Y-%m-%d"))));
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_in_the_archive_before_delivery_starts() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let tomorrow = (Utc::now() + Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+    let issue_id = publish_an_issue(&app, "Scheduled title", Some(tomorrow)).await;
+
+    let html_page = app.get_archive_html().await;
+    assert!(!html_page.contains("Scheduled title"));
+
+    let response = app.get_archived_issue(issue_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn unknown_issues_are_not_found() {
+    let app = spawn_app().await;
+
+    let response = app.get_archived_issue(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_archive_is_public() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_an_issue(&app, "Published title", None).await;
+    app.post_logout().await;
+
+    let response = app.get_archived_issue(issue_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn issue_emails_link_to_the_archived_issue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = publish_an_issue(&app, "Published title", None).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body[0]["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains(&format!(r#"/archive/{}">View in browser</a>"#, issue_id)));
+}
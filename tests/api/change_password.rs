@@ -119,3 +119,64 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn mismatched_new_passwords_do_not_change_the_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": Uuid::new_v4().to_string(),
+        "new_password_check": Uuid::new_v4().to_string(),
+    }))
+    .await;
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn new_password_must_satisfy_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let test_cases = vec![
+        (
+            "short",
+            "The new password must be at least 12 characters long.",
+        ),
+        (
+            "passwordpassword",
+            "The new password is too common - please choose a less predictable one.",
+        ),
+        (
+            app.test_user.password.as_str(),
+            "The new password must be different from the current password.",
+        ),
+    ];
+    for (new_password, error_message) in test_cases {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": new_password,
+                "new_password_check": new_password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(&format!("<p><i>{}</i></p>", error_message)),
+            "The page did not report `{}` for the new password `{}`.",
+            error_message,
+            new_password
+        );
+    }
+}
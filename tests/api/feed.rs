@@ -0,0 +1,158 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+
+// 直接写入数据库，方便控制发布时间
+async fn insert_issue(app: &TestApp, title: &str, published_days_ago: Option<i32>) -> Uuid {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, scheduled_for,
+            status, updated_at
+        )
+        VALUES (
+            $1, $2, 'Plain text', '<p>Body of ' || $2 || '</p>',
+            now() - make_interval(days => $3),
+            CASE WHEN $3::int IS NULL THEN now() + interval '1 day' END,
+            CASE WHEN $3::int IS NULL THEN 'scheduled' ELSE 'published' END,
+            now()
+        )
+        "#,
+        issue_id,
+        title,
+        published_days_ago,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    issue_id
+}
+
+#[tokio::test]
+async fn the_atom_feed_lists_the_latest_published_issues() {
+    let app = spawn_app().await;
+    insert_issue(&app, "Oldest issue", Some(3)).await;
+    let older = insert_issue(&app, "Older issue", Some(2)).await;
+    let newest = insert_issue(&app, "Newest issue", Some(1)).await;
+    insert_issue(&app, "Scheduled issue", None).await;
+
+    let response = app.get_feed("feed.atom", &[]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/atom+xml; charset=utf-8"
+    );
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#));
+    assert!(body.contains(&format!("<id>urn:uuid:{}</id>", newest)));
+    assert!(body.contains(&format!("/archive/{}\"/>", older)));
+    assert!(body.contains("&lt;p&gt;Body of Newest issue&lt;/p&gt;"));
+    // 条目上限在测试中被设置为 2
+    assert!(!body.contains("Oldest issue"));
+    assert!(!body.contains("Scheduled issue"));
+    assert!(body.find("Newest issue").unwrap() < body.find("Older issue").unwrap());
+}
+
+#[tokio::test]
+async fn the_rss_feed_lists_the_latest_published_issues() {
+    let app = spawn_app().await;
+    insert_issue(&app, "Oldest issue", Some(3)).await;
+    insert_issue(&app, "Older issue", Some(2)).await;
+    let newest = insert_issue(&app, "Newest issue", Some(1)).await;
+
+    let response = app.get_feed("feed.rss", &[]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/rss+xml; charset=utf-8"
+    );
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"<rss version="2.0">"#));
+    assert!(body.contains(&format!("/archive/{}</link>", newest)));
+    assert!(body.contains("<lastBuildDate>"));
+    assert!(body.contains("<title>Older issue</title>"));
+    assert!(!body.contains("Oldest issue"));
+}
+
+#[tokio::test]
+async fn an_empty_feed_is_still_valid() {
+    let app = spawn_app().await;
+
+    let response = app.get_feed("feed.atom", &[]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("Last-Modified").is_none());
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<updated>1970-01-01T00:00:00+00:00</updated>"));
+}
+
+#[tokio::test]
+async fn a_matching_etag_returns_304() {
+    let app = spawn_app().await;
+    insert_issue(&app, "First issue", Some(1)).await;
+
+    for feed in ["feed.atom", "feed.rss"] {
+        let response = app.get_feed(feed, &[]).await;
+        let etag = response
+            .headers()
+            .get("ETag")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let response = app.get_feed(feed, &[("If-None-Match", &etag)]).await;
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(response.headers().get("ETag").unwrap(), etag.as_str());
+        assert!(response.text().await.unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn the_etag_changes_when_an_issue_is_published() {
+    let app = spawn_app().await;
+    insert_issue(&app, "First issue", Some(1)).await;
+    let response = app.get_feed("feed.atom", &[]).await;
+    let etag = response
+        .headers()
+        .get("ETag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    insert_issue(&app, "Second issue", Some(0)).await;
+    let response = app.get_feed("feed.atom", &[("If-None-Match", &etag)]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(response.headers().get("ETag").unwrap(), etag.as_str());
+}
+
+#[tokio::test]
+async fn if_modified_since_is_honoured() {
+    let app = spawn_app().await;
+    insert_issue(&app, "First issue", Some(1)).await;
+    let response = app.get_feed("feed.rss", &[]).await;
+    let last_modified = response
+        .headers()
+        .get("Last-Modified")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .get_feed("feed.rss", &[("If-Modified-Since", &last_modified)])
+        .await;
+    assert_eq!(response.status().as_u16(), 304);
+
+    let response = app
+        .get_feed(
+            "feed.rss",
+            &[("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
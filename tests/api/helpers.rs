@@ -1,15 +1,26 @@
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
 use once_cell::sync::Lazy;
+use secrecy::Secret;
 use sqlx::PgPool;
 use uuid::Uuid;
-use wiremock::MockServer;
-use zero2prod::configuration::get_configuration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::configuration::{
+    get_configuration, EmailTransportSettings, IdempotencySettings, IssueDeliverySettings,
+    LoginThrottlingSettings,
+};
+use zero2prod::confirmation_email_worker::{try_send_confirmation_email, ConfirmationOutcome};
 use zero2prod::email_client::EmailClient;
+use zero2prod::idempotency::{try_delete_expired_idempotency_keys, CleanupOutcome};
 use zero2prod::startup::{get_connection_pool, Application};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::issue_scheduler::{try_publish_scheduled_issue, SchedulingOutcome};
 
 
 // 确保“tracing”堆栈仅使用“once_cell”初始化一次
@@ -40,13 +51,48 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub hmac_secret: Secret<String>,
+    pub issue_delivery: IssueDeliverySettings,
+    pub idempotency: IdempotencySettings,
+    pub login_throttling: LoginThrottlingSettings,
 }
 
 impl TestApp {
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.address,
+                &self.hmac_secret,
+                &self.issue_delivery,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+    pub async fn dispatch_all_pending_confirmation_emails(&self) {
+        loop {
+            if let ConfirmationOutcome::EmptyQueue = try_send_confirmation_email(
+                &self.db_pool,
+                &self.email_client,
+                &self.address,
+                &self.issue_delivery,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+    pub async fn delete_expired_idempotency_keys(&self) {
+        loop {
+            if let CleanupOutcome::NothingExpired =
+                try_delete_expired_idempotency_keys(&self.db_pool, &self.idempotency)
                     .await
                     .unwrap()
             {
@@ -54,6 +100,17 @@ impl TestApp {
             }
         }
     }
+
+    pub async fn publish_due_scheduled_issues(&self) {
+        loop {
+            if let SchedulingOutcome::NothingDue =
+                try_publish_scheduled_issue(&self.db_pool).await.unwrap()
+            {
+                break;
+            }
+        }
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/subscriptions", &self.address))
@@ -64,6 +121,59 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_newsletters_as(
+        &self,
+        username: &str,
+        password: &str,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/newsletters", &self.address))
+            .basic_auth(username, Some(password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletters_with_token(
+        &self,
+        token: &str,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/newsletters", &self.address))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletters_with_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/newsletters", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_newsletter_issue_status(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/newsletters/{}", &self.address, issue_id))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn post_newsletters(&self, body: serde_json::Value) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/newsletters", &self.address))
@@ -94,11 +204,40 @@ impl TestApp {
         };
 
         let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        // let plain_text = get_link(&body["MessageStream"].as_str().unwrap());
-        let plain_text = html.clone();
+        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 
+    /// 从发出的期刊邮件中提取 `List-Unsubscribe` 头携带的退订链接
+    ///
+    /// 期刊通过批量接口发送，这里取批次中的第一封邮件
+    pub fn get_unsubscribe_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let header = body[0]["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == "List-Unsubscribe")
+            .unwrap();
+        let raw_link = header["Value"]
+            .as_str()
+            .unwrap()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        let unsubscribe_link = reqwest::Url::parse(raw_link).unwrap();
+        assert_eq!(unsubscribe_link.host_str().unwrap(), "127.0.0.1");
+        unsubscribe_link
+    }
+
+    pub async fn post_unsubscribe(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/subscriptions/unsubscribe", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     // 提供一个辅助方法来检索其用户名和密码
     pub async fn test_user(&self) -> (String, String) {
         let row = sqlx::query!("SELECT username, password_hash FROM users LIMIT 1",)
@@ -189,6 +328,176 @@ impl TestApp {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn get_delivery_failures_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/delivery-failures", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_newsletter_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/admin/newsletters/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_issue_html(&self, issue_id: Uuid) -> String {
+        self.get_newsletter_issue(issue_id).await.text().await.unwrap()
+    }
+
+    pub async fn get_newsletter_history(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(&format!(
+                "{}/admin/newsletters/history?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_history_html(&self, query: &str) -> String {
+        self.get_newsletter_history(query).await.text().await.unwrap()
+    }
+
+    pub async fn get_drafts_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/newsletters/drafts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_draft(&self, draft_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(&format!(
+                "{}/admin/newsletters/drafts/{}",
+                &self.address, draft_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_create_draft<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/admin/newsletters/drafts", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `action` 为空时是普通保存，否则是 `autosave` 或 `delete`
+    pub async fn post_draft_action<T>(
+        &self,
+        draft_id: Uuid,
+        action: &str,
+        body: &T,
+    ) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        let mut url = format!("{}/admin/newsletters/drafts/{}", &self.address, draft_id);
+        if !action.is_empty() {
+            url = format!("{}/{}", url, action);
+        }
+        self.api_client
+            .post(&url)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_preview_newsletter<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/admin/newsletters/preview", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_send_test_newsletter<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/admin/newsletters/test", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_requeue_delivery_failure<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!(
+                "{}/admin/delivery-failures/requeue",
+                &self.address
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_scheduled_newsletters_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/newsletters/scheduled", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_reschedule_newsletter<T>(&self, issue_id: Uuid, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!(
+                "{}/admin/newsletters/scheduled/{}/reschedule",
+                &self.address, issue_id
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_scheduled_newsletter(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(&format!(
+                "{}/admin/newsletters/scheduled/{}/cancel",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_publish_newsletter<T>(&self, body: &T) -> reqwest::Response
     where
         T: serde::Serialize,
@@ -200,6 +509,260 @@ impl TestApp {
             .await
             .expect("Failed to execute request.")
     }
+
+    pub async fn get_forgot_password_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/login/forgot", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_forgot_password(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/login/forgot", &self.address))
+            .form(&serde_json::json!({ "username": username }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/login/reset", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users_html(&self) -> String {
+        self.get_admin_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user<T>(&self, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/admin/users", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_user_role(&self, user_id: Uuid, role: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/users/{}/role", &self.address, user_id))
+            .form(&serde_json::json!({ "role": role }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_deactivate_user(&self, user_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(&format!(
+                "{}/admin/users/{}/deactivate",
+                &self.address, user_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_api_token(&self, name: &str, scope: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/api-tokens", &self.address))
+            .form(&serde_json::json!({ "name": name, "scope": scope }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_api_token(&self, api_token_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(&format!(
+                "{}/admin/api-tokens/{}/revoke",
+                &self.address, api_token_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_subscribers_html(&self, query: &str) -> String {
+        self.get_admin_subscribers(query).await.text().await.unwrap()
+    }
+
+    /// `action` 是 `confirm`、`unsubscribe`、`delete` 或 `resend-confirmation`
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(&format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_import_subscribers(
+        &self,
+        csv: &str,
+        mode: &str,
+        dry_run: bool,
+    ) -> reqwest::Response {
+        let file = reqwest::multipart::Part::text(csv.to_string())
+            .file_name("subscribers.csv")
+            .mime_str("text/csv")
+            .unwrap();
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file)
+            .text("mode", mode.to_string());
+        if dry_run {
+            form = form.text("dry_run", "on");
+        }
+        self.api_client
+            .post(&format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_export_subscribers(&self) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/admin/subscribers/export", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_archive_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/archive", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_archived_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/archive/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_feed(&self, feed: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(&format!("{}/{}", &self.address, feed));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_lists_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_list(&self, slug: &str, name: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/lists", &self.address))
+            .form(&serde_json::json!({ "slug": slug, "name": name }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_factor_settings_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_confirm_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/two-factor/disable", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_login_second_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/login/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
 }
 // 小辅助函数 - 我们将在本章和下一章中多次进行此检查
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
@@ -211,14 +774,20 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role: role.to_string(),
             // username: "admin".to_string(),
             // password: "everythinghastostartsomewhere".to_string(),
         }
@@ -232,7 +801,7 @@ impl TestUser {
         .await;
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut OsRng);
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
@@ -246,11 +815,12 @@ impl TestUser {
         dbg!(&password_hash);
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.role,
         )
         .execute(pool)
         .await
@@ -271,8 +841,20 @@ pub async fn spawn_app() -> TestApp {
     let configuration = {
         let mut c = get_configuration().expect("Failed to read configuration.");
 
+        // 保证测试数据库的表结构是最新的
+        c.database.migrate_on_startup = true;
         // 使用模拟服务器作为电子邮件 API
-        c.email_client.base_url = email_server.uri();
+        c.email_client.transport = EmailTransportSettings::Postmark {
+            base_url: email_server.uri(),
+            authorization_token: Secret::new("my-secret-token".to_string()),
+        };
+        // 固定锁定阈值并缩短延迟，让登录限流的测试既确定又不拖慢测试
+        c.login_throttling.max_failures_per_username = 3;
+        c.login_throttling.max_failures_per_ip = 10;
+        c.login_throttling.base_delay_milliseconds = 10;
+        c.login_throttling.max_delay_milliseconds = 50;
+        // 较小的条目上限，方便测试 feed 只包含最新的几期
+        c.feed.item_limit = 2;
         c
     };
 
@@ -299,7 +881,100 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        hmac_secret: configuration.application.hmac_secret,
+        issue_delivery: configuration.issue_delivery,
+        idempotency: configuration.idempotency,
+        login_throttling: configuration.login_throttling,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
+
+/// 模拟 Postmark 的 `/email/batch` 接口，为请求中的每封邮件返回一条结果
+pub struct PostmarkBatchResponder {
+    n_rejected: usize,
+}
+
+impl PostmarkBatchResponder {
+    pub fn accept_all() -> Self {
+        Self { n_rejected: 0 }
+    }
+
+    /// 拒绝每个批次中的前 `n` 封邮件，其余的照常接受
+    pub fn reject_first(n: usize) -> Self {
+        Self { n_rejected: n }
+    }
+}
+
+impl wiremock::Respond for PostmarkBatchResponder {
+    fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+        let emails: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+        let results: Vec<serde_json::Value> = emails
+            .iter()
+            .enumerate()
+            .map(|(i, email)| {
+                if i < self.n_rejected {
+                    serde_json::json!({
+                        "ErrorCode": 406,
+                        "Message": "You tried to send to a recipient that has been marked as inactive.",
+                        "To": email["To"],
+                    })
+                } else {
+                    serde_json::json!({
+                        "ErrorCode": 0,
+                        "Message": "OK",
+                        "MessageID": Uuid::new_v4().to_string(),
+                        "To": email["To"],
+                    })
+                }
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_json(results)
+    }
+}
+
+///使用正在测试的应用程序的公共API创建未经证实的订户
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(&serde_json::json!({
+    "name": name,
+    "email": email
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // 我们现在检查模拟 Postmark 服务器收到的请求
+    // 以检索确认链接并返回它
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    // 然后我们可以重复使用相同的帮助程序，只需添加
+    // 一个额外的步骤即可实际调用确认链接！
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
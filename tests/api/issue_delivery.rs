@@ -0,0 +1,316 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockBuilder, ResponseTemplate};
+
+fn when_sending_an_email() -> MockBuilder {
+    Mock::given(path("/email/batch")).and(method("POST"))
+}
+
+async fn publish_an_issue(app: &TestApp) {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "message_stream": "outbound",
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+// 跳过退避等待，让被重新调度的任务立即可以被取出
+async fn make_rescheduled_tasks_due(app: &TestApp) {
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()",)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn transient_delivery_failures_are_retried_later() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let task = sqlx::query!("SELECT n_retries, execute_after FROM issue_delivery_queue",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The failed task should still be in the queue.");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.execute_after > chrono::Utc::now());
+
+    when_sending_an_email()
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .named("Delivery retry")
+        .mount(&app.email_server)
+        .await;
+    make_rescheduled_tasks_due(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let n_pending = sqlx::query!("SELECT count(*) as \"count!\" FROM issue_delivery_queue",)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_pending, 0);
+}
+
+#[tokio::test]
+async fn an_issue_is_sent_to_all_subscribers_in_a_single_batch() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let batch_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&batch_request.body).unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn only_the_recipients_rejected_in_a_batch_are_retried() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(PostmarkBatchResponder::reject_first(1))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let batch_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&batch_request.body).unwrap();
+    let rejected_email = body[0]["To"].as_str().unwrap();
+
+    let task = sqlx::query!("SELECT subscriber_email, n_retries FROM issue_delivery_queue",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The rejected delivery should still be in the queue.");
+    assert_eq!(task.subscriber_email, rejected_email);
+    assert_eq!(task.n_retries, 1);
+}
+
+#[tokio::test]
+async fn delivery_outcomes_are_shown_on_the_issue_page() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(PostmarkBatchResponder::reject_first(1))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let delivery = sqlx::query!(
+        "SELECT newsletter_issue_id, provider_message_id FROM issue_deliveries WHERE status = 'sent'",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The successful delivery should have been recorded.");
+    let provider_message_id = delivery
+        .provider_message_id
+        .expect("The provider message id should have been recorded.");
+
+    let html_page = app
+        .get_newsletter_issue_html(delivery.newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<li>Sent: 1</li>"));
+    assert!(html_page.contains("<li>Failed: 0</li>"));
+    assert!(html_page.contains("<li>Pending: 1</li>"));
+    assert!(html_page.contains(&provider_message_id));
+}
+
+#[tokio::test]
+async fn the_issue_page_returns_404_for_an_unknown_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_newsletter_issue(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn deliveries_are_dead_lettered_after_the_maximum_number_of_attempts() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(app.issue_delivery.max_attempts as u64)
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    for _ in 0..app.issue_delivery.max_attempts {
+        make_rescheduled_tasks_due(&app).await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let n_pending = sqlx::query!("SELECT count(*) as \"count!\" FROM issue_delivery_queue",)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_pending, 0);
+    let failure = sqlx::query!("SELECT n_attempts FROM issue_delivery_failures",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The task should have been moved to the dead-letter table.");
+    assert_eq!(failure.n_attempts, app.issue_delivery.max_attempts);
+    let delivery = sqlx::query!("SELECT status FROM issue_deliveries",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The failed delivery should have been recorded.");
+    assert_eq!(delivery.status, "failed");
+}
+
+#[tokio::test]
+async fn admins_can_requeue_a_failed_delivery() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    for _ in 0..app.issue_delivery.max_attempts {
+        make_rescheduled_tasks_due(&app).await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let failure =
+        sqlx::query!("SELECT newsletter_issue_id, subscriber_email FROM issue_delivery_failures",)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    let html_page = app.get_delivery_failures_html().await;
+    assert!(html_page.contains(&failure.subscriber_email));
+
+    let response = app
+        .post_requeue_delivery_failure(&serde_json::json!({
+            "newsletter_issue_id": failure.newsletter_issue_id,
+            "subscriber_email": failure.subscriber_email,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/delivery-failures");
+
+    let html_page = app.get_delivery_failures_html().await;
+    assert!(html_page.contains("<p><i>The delivery has been re-queued.</i></p>"));
+    let task = sqlx::query!("SELECT n_retries FROM issue_delivery_queue",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The delivery should be back in the queue.");
+    assert_eq!(task.n_retries, 0);
+}
+
+#[tokio::test]
+async fn requeueing_a_delivery_that_is_already_queued_reports_it() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+    publish_an_issue(&app).await;
+    for _ in 0..app.issue_delivery.max_attempts {
+        make_rescheduled_tasks_due(&app).await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let failure =
+        sqlx::query!("SELECT newsletter_issue_id, subscriber_email FROM issue_delivery_failures",)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    // 模拟同一个收件人已经被重新入队的情况
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id, subscriber_email, n_retries, execute_after
+        )
+        VALUES ($1, $2, 0, now())
+        "#,
+        failure.newsletter_issue_id,
+        failure.subscriber_email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .post_requeue_delivery_failure(&serde_json::json!({
+            "newsletter_issue_id": failure.newsletter_issue_id,
+            "subscriber_email": failure.subscriber_email,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/delivery-failures");
+
+    let html_page = app.get_delivery_failures_html().await;
+    assert!(html_page.contains("<p><i>The delivery is already queued.</i></p>"));
+    let n_failures = sqlx::query!("SELECT count(*) as \"count!\" FROM issue_delivery_failures",)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_failures, 0);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_failed_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(&format!("{}/admin/delivery-failures", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
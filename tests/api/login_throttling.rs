@@ -0,0 +1,133 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+
+const LOCKOUT_MESSAGE: &str =
+    "<p><i>Too many failed login attempts - please try again later.</i></p>";
+
+async fn fail_login(app: &TestApp, username: &str, times: i32) {
+    for _ in 0..times {
+        let response = app
+            .post_login(&serde_json::json!({
+                "username": username,
+                "password": Uuid::new_v4().to_string(),
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+async fn log_in_test_user(app: &TestApp) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+#[tokio::test]
+async fn a_username_is_locked_out_after_too_many_failures() {
+    let app = spawn_app().await;
+    let max_failures = app.login_throttling.max_failures_per_username;
+    fail_login(&app, &app.test_user.username, max_failures).await;
+
+    // 锁定期间即使密码正确也会被拒绝
+    let response = log_in_test_user(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(LOCKOUT_MESSAGE));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_out_the_same_way() {
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+    let max_failures = app.login_throttling.max_failures_per_username;
+    fail_login(&app, &username, max_failures).await;
+
+    fail_login(&app, &username, 1).await;
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(LOCKOUT_MESSAGE));
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    let app = spawn_app().await;
+    let max_failures = app.login_throttling.max_failures_per_username;
+
+    fail_login(&app, &app.test_user.username, max_failures - 1).await;
+    let response = log_in_test_user(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    fail_login(&app, &app.test_user.username, max_failures - 1).await;
+    let response = log_in_test_user(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn logging_in_works_again_once_the_lockout_has_expired() {
+    let app = spawn_app().await;
+    let max_failures = app.login_throttling.max_failures_per_username;
+    fail_login(&app, &app.test_user.username, max_failures).await;
+
+    sqlx::query!("UPDATE login_failures SET locked_until = now() - interval '1 second'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = log_in_test_user(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_client_ip_is_locked_out_after_too_many_failures_across_usernames() {
+    let app = spawn_app().await;
+    for _ in 0..app.login_throttling.max_failures_per_ip {
+        fail_login(&app, &Uuid::new_v4().to_string(), 1).await;
+    }
+
+    // 测试用户自己没有失败过，但请求来自同一个 IP
+    let response = log_in_test_user(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(LOCKOUT_MESSAGE));
+}
+
+#[tokio::test]
+async fn basic_auth_on_newsletters_is_throttled_too() {
+    let app = spawn_app().await;
+    let max_failures = app.login_throttling.max_failures_per_username;
+    for _ in 0..max_failures {
+        let response = app
+            .post_newsletters_as(
+                &app.test_user.username,
+                &Uuid::new_v4().to_string(),
+                newsletter_body(),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_newsletters_as(
+            &app.test_user.username,
+            &app.test_user.password,
+            newsletter_body(),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+}
@@ -2,7 +2,22 @@ mod health_check;
 mod helpers;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod subscriber_lists;
 mod newsletter;
+mod issue_delivery;
+mod scheduled_newsletters;
+mod newsletter_history;
+mod newsletter_drafts;
 mod login;
+mod login_throttling;
 mod change_password;
 mod admin_dashboard;
+mod admin_users;
+mod admin_subscribers;
+mod admin_subscribers_csv;
+mod api_tokens;
+mod archive;
+mod feed;
+mod password_reset;
+mod two_factor;
@@ -1,7 +1,7 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
-use fake::faker::internet::en::SafeEmail;
-use fake::faker::name::en::Name;
-use fake::Fake;
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    PostmarkBatchResponder, TestApp, TestUser,
+};
 use std::time::Duration;
 use uuid::Uuid;
 use wiremock::matchers::{any, method, path};
@@ -110,6 +110,116 @@ async fn newsletter_creation_is_idempotent() {
     assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
 }
 
+#[tokio::test]
+async fn a_duplicate_request_gets_409_if_the_original_is_still_in_flight() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // 模拟一个仍在处理中的请求：持有该幂等键的行锁，但不提交
+    let mut in_flight = app.db_pool.begin().await.unwrap();
+    sqlx::query!(
+        "INSERT INTO idempotency (user_id, idempotency_key, created_at) VALUES ($1, $2, now())",
+        app.test_user.user_id,
+        idempotency_key
+    )
+    .execute(&mut *in_flight)
+    .await
+    .unwrap();
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "message_stream": "outbound",
+        "idempotency_key": idempotency_key
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(response.headers().get("Retry-After").is_some());
+    in_flight.rollback().await.unwrap();
+}
+
+// 把测试用户的幂等键挪到保留时间之外
+async fn expire_idempotency_key(app: &TestApp, idempotency_key: &str) {
+    let expired_at = chrono::Utc::now()
+        - chrono::Duration::from_std(app.idempotency.retention()).unwrap()
+        - chrono::Duration::hours(1);
+    sqlx::query!(
+        "UPDATE idempotency SET created_at = $1 WHERE user_id = $2 AND idempotency_key = $3",
+        expired_at,
+        app.test_user.user_id,
+        idempotency_key
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn an_expired_idempotency_key_is_treated_as_a_new_request() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let title = Uuid::new_v4().to_string();
+    let idempotency_key = Uuid::new_v4().to_string();
+    let newsletter_request_body = serde_json::json!({
+        "title": title,
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "message_stream": "outbound",
+        "idempotency_key": idempotency_key
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    expire_idempotency_key(&app, &idempotency_key).await;
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let n_issues = sqlx::query!(
+        "SELECT count(*) as \"count!\" FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_issues, 2);
+}
+
+#[tokio::test]
+async fn only_expired_idempotency_keys_are_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let expired_key = Uuid::new_v4().to_string();
+    let fresh_key = Uuid::new_v4().to_string();
+    for idempotency_key in [&expired_key, &fresh_key] {
+        let newsletter_request_body = serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": idempotency_key
+        });
+        let response = app.post_publish_newsletter(&newsletter_request_body).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+    expire_idempotency_key(&app, &expired_key).await;
+
+    app.delete_expired_idempotency_keys().await;
+
+    let saved_keys = sqlx::query!(
+        "SELECT idempotency_key FROM idempotency WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved_keys.len(), 1);
+    assert_eq!(saved_keys[0].idempotency_key, fresh_key);
+}
+
 // 传递一个有效的用户名和一个不正确的密码。
 #[tokio::test]
 async fn invalid_password_is_rejected() {
@@ -226,23 +336,16 @@ async fn newsletters_are_delivered_to_unconfirmed_subscribers() {
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
 
-    Mock::given(path("/email"))
+    Mock::given(path("/email/batch"))
         .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
+        .respond_with(PostmarkBatchResponder::accept_all())
         .expect(1)
         .mount(&app.email_server)
         .await;
 
-    let newsletter_request_body = serde_json::json!({
-        "title": "Newsletter title",
-        "content": {
-            "text": "Newsletter body as plain text",
-            "html": "<p>Newsletter body as HTML</p>",
-        }
-    });
-
-    let response = app.post_newsletters(newsletter_request_body).await;
-    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_newsletters(api_newsletter_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
 }
 
 #[tokio::test]
@@ -256,60 +359,112 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
         .mount(&app.email_server)
         .await;
 
-    let newsletter_request_body = serde_json::json!({
+    let response = app.post_newsletters(api_newsletter_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+fn api_newsletter_body() -> serde_json::Value {
+    serde_json::json!({
         "title": "Newsletter title",
         "content": {
             "text": "Newsletter body as plain text",
             "html": "<p>Newsletter body as HTML</p>",
         }
-    });
+    })
+}
+
+#[tokio::test]
+async fn the_api_answers_202_with_the_issue_id_and_a_status_url() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_newsletters(api_newsletter_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let location = response.headers()["Location"].to_str().unwrap().to_string();
+    let body: serde_json::Value = response.json().await.unwrap();
+    let issue_id = body["issue_id"].as_str().unwrap();
+    assert_eq!(body["status_url"], location.as_str());
+    assert!(location.ends_with(&format!("/newsletters/{}", issue_id)));
 
-    let response = app.post_newsletters(newsletter_request_body).await;
+    let response = app
+        .get_newsletter_issue_status(issue_id.parse().unwrap())
+        .await;
     assert_eq!(response.status().as_u16(), 200);
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["title"], "Newsletter title");
+    assert_eq!(status["deliveries"]["pending"], 1);
+    assert_eq!(status["deliveries"]["sent"], 0);
 }
 
-///使用正在测试的应用程序的公共API创建未经证实的订户
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
-    "name": name,
-    "email": email
-    }))
-    .unwrap();
+#[tokio::test]
+async fn viewers_cannot_publish_with_basic_auth() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
 
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
+    Mock::given(any())
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters_as(&viewer.username, &viewer.password, api_newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn the_status_of_an_unknown_issue_is_404() {
+    let app = spawn_app().await;
+
+    let response = app.get_newsletter_issue_status(Uuid::new_v4()).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn api_requests_with_the_same_idempotency_key_create_a_single_issue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
         .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
 
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    // 我们现在检查模拟 Postmark 服务器收到的请求
-    // 以检索确认链接并返回它
-    let email_request = &app
-        .email_server
-        .received_requests()
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = app
+        .post_newsletters_with_idempotency_key(&idempotency_key, api_newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let first_body: serde_json::Value = response.json().await.unwrap();
+
+    let response = app
+        .post_newsletters_with_idempotency_key(&idempotency_key, api_newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let second_body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(first_body, second_body);
+
+    app.dispatch_all_pending_emails().await;
+    let n_issues = sqlx::query!("SELECT count(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .pop()
-        .unwrap();
-    app.get_confirmation_links(&email_request)
+        .count;
+    assert_eq!(n_issues, 1);
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    // 然后我们可以重复使用相同的帮助程序，只需添加
-    // 一个额外的步骤即可实际调用确认链接！
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
+#[tokio::test]
+async fn an_invalid_idempotency_key_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_newsletters_with_idempotency_key(&"a".repeat(50), api_newsletter_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
 }
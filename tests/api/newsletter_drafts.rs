@@ -0,0 +1,341 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+    TestUser,
+};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn draft_body(title: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": title,
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "message_stream": "Newsletter body as plain text",
+    })
+}
+
+// 保存一份草稿并返回它的 id
+async fn create_draft(app: &TestApp, title: &str) -> Uuid {
+    let response = app.post_create_draft(&draft_body(title)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location
+        .strip_prefix("/admin/newsletters/drafts/")
+        .expect("Saving a draft should redirect to its edit page.")
+        .parse()
+        .unwrap()
+}
+
+async fn issue_status(app: &TestApp, issue_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+async fn n_queued_tasks(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+// 给测试用户设置一个邮箱，测试邮件会发到这里
+async fn set_test_user_email(app: &TestApp) -> String {
+    let email = format!("{}@example.com", Uuid::new_v4());
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to set the email of the test user.");
+    email
+}
+
+#[tokio::test]
+async fn saving_a_draft_does_not_deliver_it() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let draft_id = create_draft(&app, "Draft <title>").await;
+
+    assert_eq!(issue_status(&app, draft_id).await.as_deref(), Some("draft"));
+    assert_eq!(n_queued_tasks(&app).await, 0);
+    let html_page = app.get_drafts_html().await;
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/newsletters/drafts/{}">Draft &lt;title&gt;</a>"#,
+        draft_id
+    )));
+    app.publish_due_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn a_draft_can_be_edited_later() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "First title").await;
+
+    let response = app
+        .post_draft_action(draft_id, "", &draft_body("Second title"))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+
+    let html_page = app.get_draft(draft_id).await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains(r#"value="Second title""#));
+    assert!(html_page.contains(&format!(
+        r#"<input hidden type="text" name="draft_id" value="{}">"#,
+        draft_id
+    )));
+}
+
+#[tokio::test]
+async fn autosave_stores_the_draft_without_redirecting() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "First title").await;
+
+    let response = app
+        .post_draft_action(draft_id, "autosave", &draft_body("Autosaved title"))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let saved = sqlx::query!(
+        "SELECT title FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        draft_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.title, "Autosaved title");
+}
+
+#[tokio::test]
+async fn autosave_returns_404_for_an_unknown_draft() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_draft_action(Uuid::new_v4(), "autosave", &draft_body("Title"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_draft_can_be_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "Draft title").await;
+
+    let response = app
+        .post_draft_action(draft_id, "delete", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters/drafts");
+
+    assert_eq!(issue_status(&app, draft_id).await, None);
+    let html_page = app.get_drafts_html().await;
+    assert!(html_page.contains("<p><i>The draft has been deleted.</i></p>"));
+    assert!(!html_page.contains("Draft title"));
+}
+
+#[tokio::test]
+async fn publishing_a_draft_delivers_it() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let draft_id = create_draft(&app, "Draft title").await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Published title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "Newsletter body as plain text",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "draft_id": draft_id.to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let saved = sqlx::query!(
+        "SELECT title, status, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        draft_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.title, "Published title");
+    assert_eq!(saved.status, "published");
+    assert!(saved.published_at.is_some());
+    assert!(!app.get_drafts_html().await.contains("Published title"));
+}
+
+#[tokio::test]
+async fn a_draft_cannot_be_published_twice() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let draft_id = create_draft(&app, "Draft title").await;
+    for _ in 0..2 {
+        app.post_publish_newsletter(&serde_json::json!({
+            "title": "Draft title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "Newsletter body as plain text",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "draft_id": draft_id.to_string(),
+        }))
+        .await;
+    }
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_drafts_html().await;
+    assert!(html_page
+        .contains("<p><i>The draft no longer exists or has already been published.</i></p>"));
+}
+
+#[tokio::test]
+async fn scheduled_issue_routes_ignore_drafts() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "Draft title").await;
+
+    app.post_reschedule_newsletter(
+        draft_id,
+        &serde_json::json!({ "scheduled_for": "2030-01-01T10:00" }),
+    )
+    .await;
+    app.post_cancel_scheduled_newsletter(draft_id).await;
+
+    assert_eq!(issue_status(&app, draft_id).await.as_deref(), Some("draft"));
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(!html_page.contains("Draft title"));
+}
+
+#[tokio::test]
+async fn the_preview_renders_both_versions_of_the_content() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_preview_newsletter(&serde_json::json!({
+            "title": "Preview <title>",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "Plain <text> body",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Preview &lt;title&gt;</h1>"));
+    assert!(html_page.contains("&lt;p&gt;Newsletter body as HTML&lt;/p&gt;"));
+    assert!(html_page.contains("<pre>Plain &lt;text&gt; body</pre>"));
+}
+
+#[tokio::test]
+async fn a_test_email_is_sent_only_to_the_logged_in_user() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = set_test_user_email(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_send_test_newsletter(&draft_body("Test title"))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains(&format!("A test email has been sent to {}.", email)));
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], email);
+    assert_eq!(body["Subject"], "[Test] Test title");
+    assert_eq!(n_queued_tasks(&app).await, 0);
+    let n_issues = sqlx::query!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn no_test_email_is_sent_to_users_without_an_email_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_send_test_newsletter(&draft_body("Test title"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn viewers_cannot_save_drafts_or_send_test_emails() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app.post_create_draft(&draft_body("Draft title")).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app
+        .post_send_test_newsletter(&draft_body("Test title"))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,117 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::Mock;
+
+async fn publish_an_issue(app: &TestApp, title: &str, scheduled_for: &str) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": title,
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "scheduled_for": scheduled_for,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_newsletter_history() {
+    let app = spawn_app().await;
+
+    let response = app.get_newsletter_history("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_history_lists_issues_with_their_author_and_status() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let tomorrow = (Utc::now() + Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+
+    let published_id = publish_an_issue(&app, "Published <title>", "").await;
+    let scheduled_id = publish_an_issue(&app, "Scheduled title", &tomorrow).await;
+
+    let html_page = app.get_newsletter_history_html("").await;
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/newsletters/{}">Published &lt;title&gt;</a>"#,
+        published_id
+    )));
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/newsletters/{}">Scheduled title</a>"#,
+        scheduled_id
+    )));
+    assert!(html_page.contains(&format!("<td>{}</td>", app.test_user.username)));
+    assert!(html_page.contains("<td>published</td>"));
+    assert!(html_page.contains("<td>scheduled</td>"));
+    assert!(html_page.contains("Page 1 of 1 (2 issues)"));
+}
+
+#[tokio::test]
+async fn the_history_shows_the_delivery_progress() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::reject_first(1))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_an_issue(&app, "Newsletter title", "").await;
+    let html_page = app.get_newsletter_history_html("").await;
+    assert!(html_page.contains("0 sent, 0 failed, 0 skipped, 2 pending"));
+
+    app.dispatch_all_pending_emails().await;
+
+    // 被拒绝的收件人会留在队列中等待重试
+    let html_page = app.get_newsletter_history_html("").await;
+    assert!(html_page.contains("1 sent, 0 failed, 0 skipped, 1 pending"));
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_marked_as_published_once_delivered() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let tomorrow = (Utc::now() + Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+    let issue_id = publish_an_issue(&app, "Scheduled title", &tomorrow).await;
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_for = now() WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.publish_due_scheduled_issues().await;
+
+    let saved = sqlx::query!(
+        "SELECT status, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.status, "published");
+    assert!(saved.published_at.is_some());
+}
@@ -0,0 +1,190 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+// 给测试用户设置一个邮箱，只有设置了邮箱的用户才能重置密码
+async fn set_test_user_email(app: &TestApp) -> String {
+    let email = format!("{}@example.com", Uuid::new_v4());
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to set the email of the test user.");
+    email
+}
+
+// 申请重置密码并返回邮件中的重置链接
+async fn request_reset_link(app: &TestApp, username: &str) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_forgot_password(username).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).html
+}
+
+fn token_of(reset_link: &reqwest::Url) -> String {
+    reset_link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[tokio::test]
+async fn unknown_usernames_get_the_same_answer_and_no_email() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_forgot_password(&Uuid::new_v4().to_string()).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+
+    let html_page = app.get_forgot_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>If the account exists, a link to reset its password has been sent to its email address.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn a_user_can_reset_their_password_with_the_emailed_link() {
+    let app = spawn_app().await;
+    let email = set_test_user_email(&app).await;
+    let reset_link = request_reset_link(&app, &email).await;
+
+    let response = reqwest::get(reset_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token_of(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>Your password has been reset. Please log in with your new password.</i></p>"
+    ));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    let reset_link = request_reset_link(&app, &app.test_user.username).await;
+    let new_password = Uuid::new_v4().to_string();
+    let body = serde_json::json!({
+        "token": token_of(&reset_link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+
+    let response = app.post_reset_password(&body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_reset_password(&body).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+    let html_page = app.get_forgot_password_html().await;
+    assert!(html_page.contains("<p><i>This password reset link is invalid or has expired.</i></p>"));
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    let reset_link = request_reset_link(&app, &app.test_user.username).await;
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .api_client
+        .get(reset_link)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/login/forgot");
+}
+
+#[tokio::test]
+async fn resetting_the_password_ends_existing_sessions() {
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let reset_link = request_reset_link(&app, &app.test_user.username).await;
+    let new_password = Uuid::new_v4().to_string();
+    app.post_reset_password(&serde_json::json!({
+        "token": token_of(&reset_link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_reset_password_must_satisfy_the_password_policy() {
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    let reset_link = request_reset_link(&app, &app.test_user.username).await;
+    let token = token_of(&reset_link);
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": "short",
+            "new_password_check": "short",
+        }))
+        .await;
+    assert_is_redirect_to(&response, &format!("/login/reset?token={}", token));
+
+    let html_page = app
+        .api_client
+        .get(reset_link)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        html_page.contains("<p><i>The new password must be at least 12 characters long.</i></p>")
+    );
+}
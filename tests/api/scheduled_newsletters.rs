@@ -0,0 +1,156 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn tomorrow() -> String {
+    (Utc::now() + Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
+
+async fn schedule_an_issue(app: &TestApp, scheduled_for: &str) -> reqwest::Response {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Scheduled newsletter title",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "message_stream": "outbound",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "scheduled_for": scheduled_for,
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await
+}
+
+async fn scheduled_issue_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues WHERE published_at IS NULL",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the scheduled issue.")
+        .newsletter_issue_id
+}
+
+async fn make_scheduled_issues_due(app: &TestApp) {
+    sqlx::query!("UPDATE newsletter_issues SET scheduled_for = now() WHERE published_at IS NULL",)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_delivered_before_their_time() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = schedule_an_issue(&app, &tomorrow()).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been scheduled for"));
+
+    app.publish_due_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(html_page.contains("Scheduled newsletter title"));
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_delivered_once_they_are_due() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    schedule_an_issue(&app, &tomorrow()).await;
+    make_scheduled_issues_due(&app).await;
+    app.publish_due_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(!html_page.contains("Scheduled newsletter title"));
+}
+
+#[tokio::test]
+async fn cancelled_issues_are_never_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    schedule_an_issue(&app, &tomorrow()).await;
+    let issue_id = scheduled_issue_id(&app).await;
+
+    let response = app.post_cancel_scheduled_newsletter(issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/scheduled");
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(html_page.contains("<p><i>The scheduled newsletter issue has been cancelled.</i></p>"));
+
+    make_scheduled_issues_due(&app).await;
+    app.publish_due_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn scheduled_issues_can_be_rescheduled() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    schedule_an_issue(&app, &tomorrow()).await;
+    let issue_id = scheduled_issue_id(&app).await;
+    let next_week = Utc::now() + Duration::days(7);
+
+    let response = app
+        .post_reschedule_newsletter(
+            issue_id,
+            &serde_json::json!({
+                "scheduled_for": next_week.format("%Y-%m-%dT%H:%M").to_string(),
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters/scheduled");
+
+    let saved = sqlx::query!(
+        r#"SELECT scheduled_for as "scheduled_for!" FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved.scheduled_for > Utc::now() + Duration::days(6));
+}
+
+#[tokio::test]
+async fn issues_cannot_be_scheduled_in_the_past() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let yesterday = (Utc::now() - Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+
+    let response = schedule_an_issue(&app, &yesterday).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("is in the past."));
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(!html_page.contains("Scheduled newsletter title"));
+}
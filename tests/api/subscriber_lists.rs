@@ -0,0 +1,400 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, PostmarkBatchResponder, TestApp,
+    TestUser,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_list(app: &TestApp, slug: &str) {
+    sqlx::query!(
+        "INSERT INTO subscriber_lists (list_id, slug, name) VALUES ($1, $2, $2)",
+        Uuid::new_v4(),
+        slug
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn subscription_body(email: &str, list: &str) -> String {
+    serde_urlencoded::to_string(&serde_json::json!({
+        "name": "le guin",
+        "email": email,
+        "list": list,
+    }))
+    .unwrap()
+}
+
+// 订阅指定列表并点击确认链接
+async fn subscribe_to(app: &TestApp, email: &str, list: &str) {
+    let body = subscription_body(email, list);
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let n_sent_before = app.email_server.received_requests().await.unwrap().len();
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // 已经是该列表成员的订阅者不会收到确认邮件
+    let mut received_requests = app.email_server.received_requests().await.unwrap();
+    if received_requests.len() > n_sent_before {
+        let email_request = received_requests.pop().unwrap();
+        let confirmation_links = app.get_confirmation_links(&email_request);
+        reqwest::get(confirmation_links.html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+}
+
+async fn list_members(app: &TestApp, slug: &str) -> Vec<String> {
+    sqlx::query!(
+        r#"
+        SELECT s.email
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        JOIN subscriber_lists l ON l.list_id = m.list_id
+        WHERE l.slug = $1 AND m.status = 'confirmed'
+        ORDER BY s.email
+        "#,
+        slug
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.email)
+    .collect()
+}
+
+async fn delivered_recipients(app: &TestApp) -> Vec<String> {
+    let mut recipients: Vec<String> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/email/batch")
+        .flat_map(|r| serde_json::from_slice::<Vec<serde_json::Value>>(&r.body).unwrap())
+        .map(|email| email["To"].as_str().unwrap().to_string())
+        .collect();
+    recipients.sort();
+    recipients
+}
+
+#[tokio::test]
+async fn subscribers_without_a_list_join_the_default_list() {
+    let app = spawn_app().await;
+
+    create_confirmed_subscriber(&app).await;
+
+    assert_eq!(list_members(&app, "newsletter").await.len(), 1);
+}
+
+#[tokio::test]
+async fn subscribers_can_join_a_named_list() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+
+    subscribe_to(&app, "ursula@example.com", "weekly-digest").await;
+
+    assert_eq!(
+        list_members(&app, "weekly-digest").await,
+        vec!["ursula@example.com".to_string()]
+    );
+    assert!(list_members(&app, "newsletter").await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            "name=le%20guin&email=ursula%40example.com&list=no-such-list",
+            "unknown list",
+        ),
+        (
+            "name=le%20guin&email=ursula%40example.com&list=Not%20A%20Slug",
+            "invalid slug",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_subscriptions(body.into()).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request for an {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_confirmed_subscriber_must_confirm_joining_another_list() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(subscription_body("ursula@example.com", "weekly-digest"))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // 点击确认链接之前不会收到该列表的期刊
+    assert!(list_members(&app, "weekly-digest").await.is_empty());
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(list_members(&app, "weekly-digest").await.len(), 1);
+    assert_eq!(list_members(&app, "newsletter").await.len(), 1);
+}
+
+#[tokio::test]
+async fn an_unconfirmed_list_membership_does_not_receive_issues() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(subscription_body("ursula@example.com", "weekly-digest"))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_weekly-digest": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn an_existing_member_of_a_list_gets_no_new_confirmation_email() {
+    let app = spawn_app().await;
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+
+    let n_sent_before = app.email_server.received_requests().await.unwrap().len();
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+
+    let n_sent_after = app.email_server.received_requests().await.unwrap().len();
+    assert_eq!(n_sent_before, n_sent_after);
+    assert_eq!(list_members(&app, "newsletter").await.len(), 1);
+}
+
+#[tokio::test]
+async fn an_issue_is_only_delivered_to_the_targeted_lists() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+    create_list(&app, "announcements").await;
+    subscribe_to(&app, "digest@example.com", "weekly-digest").await;
+    subscribe_to(&app, "announcements@example.com", "announcements").await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_weekly-digest": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(
+        delivered_recipients(&app).await,
+        vec!["digest@example.com".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn subscribers_on_several_targeted_lists_receive_a_single_email() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+    subscribe_to(&app, "ursula@example.com", "weekly-digest").await;
+    subscribe_to(&app, "digest@example.com", "weekly-digest").await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_newsletter": "on",
+            "list_weekly-digest": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(
+        delivered_recipients(&app).await,
+        vec![
+            "digest@example.com".to_string(),
+            "ursula@example.com".to_string()
+        ]
+    );
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "message_stream": "outbound",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_no-such-list": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>There is no list called no-such-list.</i></p>"));
+    let n_issues = sqlx::query!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn the_json_api_can_target_lists() {
+    let app = spawn_app().await;
+    create_list(&app, "weekly-digest").await;
+    subscribe_to(&app, "digest@example.com", "weekly-digest").await;
+    subscribe_to(&app, "ursula@example.com", "newsletter").await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(PostmarkBatchResponder::accept_all())
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            },
+            "lists": ["weekly-digest"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(
+        delivered_recipients(&app).await,
+        vec!["digest@example.com".to_string()]
+    );
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            },
+            "lists": ["no-such-list"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn editors_can_create_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_create_list("weekly-digest", "Weekly digest").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_subscriber_lists_html().await;
+    assert!(html_page.contains("<p><i>The list weekly-digest has been created.</i></p>"));
+    assert!(html_page.contains("<td>Weekly digest</td>"));
+
+    app.post_create_list("weekly-digest", "Another digest")
+        .await;
+    let html_page = app.get_subscriber_lists_html().await;
+    assert!(html_page.contains("<p><i>A list with the slug weekly-digest already exists.</i></p>"));
+}
+
+#[tokio::test]
+async fn viewers_cannot_create_lists() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app.post_create_list("weekly-digest", "Weekly digest").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    let n_lists = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM subscriber_lists WHERE slug = 'weekly-digest'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_lists, 0);
+}
@@ -0,0 +1,211 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use zero2prod::authentication::totp;
+
+// 从设置页面中取出待确认的 base32 密钥
+fn extract_secret(html_page: &str) -> String {
+    let start = html_page.find("Secret: <code>").unwrap() + "Secret: <code>".len();
+    let end = start + html_page[start..].find("</code>").unwrap();
+    html_page[start..end].to_string()
+}
+
+fn extract_recovery_codes(html_page: &str) -> Vec<String> {
+    html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s[..s.find("</code>").unwrap()].to_string())
+        .collect()
+}
+
+fn current_code(secret: &str) -> String {
+    totp(secret, "").unwrap().generate_current().unwrap()
+}
+
+// 登录测试用户并完成两步验证的登记，返回密钥和恢复码
+async fn enrol_test_user(app: &TestApp) -> (String, Vec<String>) {
+    app.test_user.login(app).await;
+    let secret = extract_secret(&app.get_two_factor_settings_html().await);
+    let response = app.post_confirm_two_factor(&current_code(&secret)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let recovery_codes = extract_recovery_codes(&response.text().await.unwrap());
+    app.post_logout().await;
+    (secret, recovery_codes)
+}
+
+async fn log_in_with_password(app: &TestApp) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn the_enrolment_page_shows_a_qr_code_and_an_otpauth_uri() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("<svg"));
+    assert!(html_page.contains("otpauth://totp/"));
+
+    // 刷新页面不会更换密钥
+    let secret = extract_secret(&html_page);
+    let html_page = app.get_two_factor_settings_html().await;
+    assert_eq!(extract_secret(&html_page), secret);
+}
+
+#[tokio::test]
+async fn confirming_with_a_valid_code_enables_two_factor_and_shows_recovery_codes() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enrol_test_user(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    app.test_user.login(&app).await;
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("<p>Two-factor authentication is enabled.</p>"));
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_enable_two_factor() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.get_two_factor_settings_html().await;
+
+    let response = app.post_confirm_two_factor("000000x").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("<p><i>The verification code is incorrect.</i></p>"));
+    let totp_secret = sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .totp_secret;
+    assert!(totp_secret.is_none());
+}
+
+#[tokio::test]
+async fn a_half_authenticated_session_cannot_reach_the_admin_area() {
+    let app = spawn_app().await;
+    enrol_test_user(&app).await;
+
+    let response = log_in_with_password(&app).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_valid_totp_code_completes_the_login() {
+    let app = spawn_app().await;
+    let (secret, _) = enrol_test_user(&app).await;
+    log_in_with_password(&app).await;
+
+    let response = app.post_login_second_factor(&current_code(&secret)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_complete_the_login() {
+    let app = spawn_app().await;
+    enrol_test_user(&app).await;
+    log_in_with_password(&app).await;
+
+    let response = app.post_login_second_factor("not-a-code").await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enrol_test_user(&app).await;
+
+    log_in_with_password(&app).await;
+    let response = app.post_login_second_factor(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    log_in_with_password(&app).await;
+    let response = app.post_login_second_factor(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn two_factor_can_be_disabled_with_a_valid_code() {
+    let app = spawn_app().await;
+    let (secret, recovery_codes) = enrol_test_user(&app).await;
+    log_in_with_password(&app).await;
+    // 用恢复码登录，当前的 TOTP 验证码留给停用操作
+    app.post_login_second_factor(&recovery_codes[0]).await;
+
+    let response = app.post_disable_two_factor(&current_code(&secret)).await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    app.post_logout().await;
+
+    let response = log_in_with_password(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_totp_code_cannot_be_used_twice() {
+    let app = spawn_app().await;
+    let (secret, _) = enrol_test_user(&app).await;
+    let code = current_code(&secret);
+
+    log_in_with_password(&app).await;
+    let response = app.post_login_second_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    log_in_with_password(&app).await;
+    let response = app.post_login_second_factor(&code).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_end_the_pending_login() {
+    let app = spawn_app().await;
+    enrol_test_user(&app).await;
+    log_in_with_password(&app).await;
+
+    for _ in 0..2 {
+        let response = app.post_login_second_factor("000000").await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = app.post_login_second_factor("000000").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Too many incorrect codes - please log in again.</i></p>"));
+    let response = app.post_login_second_factor("000000").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn wrong_codes_lock_out_the_second_factor_across_logins() {
+    let app = spawn_app().await;
+    let (secret, _) = enrol_test_user(&app).await;
+
+    // 每次重新输入密码都会开始新的待验证登录，但失败次数按用户累计
+    for _ in 0..3 {
+        log_in_with_password(&app).await;
+        app.post_login_second_factor("000000").await;
+    }
+
+    log_in_with_password(&app).await;
+    let response = app.post_login_second_factor(&current_code(&secret)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page
+        .contains("<p><i>Too many failed login attempts - please try again later.</i></p>"));
+}